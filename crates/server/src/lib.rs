@@ -0,0 +1,383 @@
+//! A small synchronization server sharing the status of the roadworks within teams.
+//!
+//! It implements the protocol of the Roadwork server:
+//! `POST /setData/{team}/{source}?since={time}` with a basic authentication and the changed
+//! sync data by roadwork id as body. The server answers with the sync data of the roadworks
+//! changed after `since` and of the roadworks that were sent.
+//! A change made from an outdated state of a roadwork already changed by another member
+//! is not applied, the client then receives the current state and resolves the conflict.
+
+pub mod store;
+
+use crate::store::{FileStore, TeamData};
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use log::error;
+use roadwork_sync::{LoginResponse, SyncData};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+#[derive(Error, Debug)]
+pub enum ServerError {
+    #[error("Io Error {0:?}")]
+    IoError(#[from] std::io::Error),
+    #[error("Json Error {0:?}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Invalid name {0}")]
+    InvalidName(String),
+    #[error("Unable to start the server {0}")]
+    StartError(String),
+    #[error("Unable to create a token {0}")]
+    TokenError(String),
+}
+
+pub struct SyncServer {
+    server: Server,
+    store: FileStore,
+    /// The last server update time given, so that two changes never have the same
+    last_update_time: Mutex<u64>,
+    /// The team of each token given
+    tokens: Mutex<HashMap<String, String>>,
+}
+
+impl SyncServer {
+    /// Listen on the address, the data is kept in the folder
+    pub fn new(address: &str, folder: PathBuf) -> Result<Self, ServerError> {
+        let server = Server::http(address).map_err(|e| ServerError::StartError(e.to_string()))?;
+        Ok(Self {
+            server,
+            store: FileStore::new(folder),
+            last_update_time: Mutex::new(0),
+            tokens: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    /// Answer the requests until the server is stopped
+    pub fn run(&self) {
+        for mut request in self.server.incoming_requests() {
+            let (status, body) = match self.handle(&mut request) {
+                Ok(response) => response,
+                Err(e) => {
+                    error!("Unable to answer {}: {e}", request.url());
+                    (500, e.to_string())
+                }
+            };
+            let mut response = Response::from_string(body).with_status_code(status);
+            if status == 401 {
+                response.add_header(Header::from_bytes("WWW-Authenticate", "Basic").unwrap());
+            } else if status == 200 {
+                response
+                    .add_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+            }
+            request.respond(response).ok();
+        }
+    }
+
+    /// Stop the server, run returns once the current request is answered
+    pub fn stop(&self) {
+        self.server.unblock();
+    }
+
+    /// Returns the status code and the body of the response
+    fn handle(&self, request: &mut Request) -> Result<(u16, String), ServerError> {
+        let url = request.url().to_string();
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+        // the clients may add a slash after the server url
+        let segments = path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(urlencoding::decode)
+            .collect::<Result<Vec<_>, _>>();
+        let Ok(segments) = segments else {
+            return Ok((400, format!("Invalid path {path}")));
+        };
+        let segments: Vec<&str> = segments.iter().map(|s| s.as_ref()).collect();
+        if *request.method() != Method::Post {
+            return Ok((404, "Not found".to_string()));
+        }
+        let team = match segments[..] {
+            ["login", team] | ["setData", team, _] => team,
+            _ => return Ok((404, "Not found".to_string())),
+        };
+        match self.authenticate(request, team) {
+            Ok(true) => {}
+            Ok(false) => return Ok((401, "Unauthorized".to_string())),
+            Err(ServerError::InvalidName(name)) => return Ok((400, name)),
+            Err(e) => return Err(e),
+        }
+        let ["setData", team, source] = segments[..] else {
+            let token = self.create_token(team)?;
+            return Ok((200, serde_json::to_string(&LoginResponse { token })?));
+        };
+        let since = query
+            .split('&')
+            .find_map(|parameter| parameter.strip_prefix("since="))
+            .and_then(|since| since.parse().ok())
+            .unwrap_or(0);
+        let mut body = String::new();
+        request.as_reader().read_to_string(&mut body)?;
+        let changes: TeamData = match serde_json::from_str(&body) {
+            Ok(changes) => changes,
+            Err(e) => return Ok((400, e.to_string())),
+        };
+        let response = self.set_data(team, source, since, changes)?;
+        Ok((200, serde_json::to_string(&response)?))
+    }
+
+    /// Returns true if the request has the login and password of a member of the team,
+    /// or a token given to a member of the team
+    fn authenticate(&self, request: &Request, team: &str) -> Result<bool, ServerError> {
+        let Some(header) = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv("Authorization"))
+        else {
+            return Ok(false);
+        };
+        let authorization = header.value.as_str();
+        if let Some(token) = authorization.strip_prefix("Bearer ") {
+            return Ok(self
+                .tokens
+                .lock()
+                .unwrap()
+                .get(token)
+                .is_some_and(|token_team| token_team == team));
+        }
+        match Self::credentials(authorization) {
+            Some((login, password)) => self.store.is_member(team, &login, &password),
+            None => Ok(false),
+        }
+    }
+
+    /// Give a token to a member of the team, it is valid until the server stops
+    fn create_token(&self, team: &str) -> Result<String, ServerError> {
+        let mut bytes = [0u8; 32];
+        getrandom::fill(&mut bytes).map_err(|e| ServerError::TokenError(e.to_string()))?;
+        let token = URL_SAFE_NO_PAD.encode(bytes);
+        self.tokens
+            .lock()
+            .unwrap()
+            .insert(token.clone(), team.to_string());
+        Ok(token)
+    }
+
+    /// Apply the changes and returns the roadworks changed since the time or sent
+    fn set_data(
+        &self,
+        team: &str,
+        source: &str,
+        since: u64,
+        changes: TeamData,
+    ) -> Result<TeamData, ServerError> {
+        // the store is read and written by one request at a time
+        let mut last_update_time = self.last_update_time.lock().unwrap();
+        let mut data = self.store.load(team, source)?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let update_time = now.max(*last_update_time + 1);
+        let sent: Vec<String> = changes.keys().cloned().collect();
+        let mut changed = false;
+        for (id, mut sync_data) in changes {
+            if let Some(current) = data.get_mut(&id) {
+                if Self::is_conflict(current, &sync_data) {
+                    // the status is rejected but the notes are kept
+                    if sync_data
+                        .notes()
+                        .iter()
+                        .any(|note| !current.notes().contains(note))
+                    {
+                        current.merge_notes(sync_data.notes());
+                        current.received(update_time);
+                        changed = true;
+                    }
+                    continue;
+                }
+                sync_data.merge_notes(current.notes());
+            }
+            sync_data.received(update_time);
+            data.insert(id, sync_data);
+            changed = true;
+        }
+        if changed {
+            *last_update_time = update_time;
+            self.store.save(team, source, &data)?;
+        }
+        Ok(data
+            .into_iter()
+            .filter(|(id, sync_data)| sync_data.server_update_time() > since || sent.contains(id))
+            .collect())
+    }
+
+    /// The change was made before another member changed the status or the assignee
+    fn is_conflict(current: &SyncData, change: &SyncData) -> bool {
+        (current.status != change.status || current.assignee() != change.assignee())
+            && current.server_update_time() > change.server_update_time()
+    }
+
+    fn credentials(authorization: &str) -> Option<(String, String)> {
+        let encoded = authorization.strip_prefix("Basic ")?;
+        let decoded = String::from_utf8(STANDARD.decode(encoded).ok()?).ok()?;
+        let (login, password) = decoded.split_once(':')?;
+        Some((login.to_string(), password.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use roadwork_sync::{
+        Note, ReqwestTransport, Status, SyncClient, SyncConfig, SyncError, SyncRequest,
+        SyncResponse, Transport,
+    };
+    use std::fs;
+    use std::sync::Arc;
+
+    fn start(folder: &tempfile::TempDir) -> (Arc<SyncServer>, String) {
+        fs::write(
+            folder.path().join("users.json"),
+            r#"{"team": {"alice": "secret", "bob": "secret"}}"#,
+        )
+        .unwrap();
+        let server = Arc::new(SyncServer::new("127.0.0.1:0", folder.path().to_path_buf()).unwrap());
+        let url = format!("http://{}", server.local_addr().unwrap());
+        let running = Arc::clone(&server);
+        std::thread::spawn(move || running.run());
+        (server, url)
+    }
+
+    fn client(url: &str, login: &str) -> SyncClient<ReqwestTransport> {
+        let config = SyncConfig {
+            url: url.to_string(),
+            team: "team".to_string(),
+            login: login.to_string(),
+            password: "secret".to_string(),
+            token: None,
+        };
+        SyncClient::new(config, ReqwestTransport::default())
+    }
+
+    fn request(since: u64, changes: &[(&str, Status, u64)]) -> SyncRequest {
+        let mut request = SyncRequest::new("France-Paris", since);
+        for (id, status, time) in changes {
+            let mut sync_data = SyncData::default();
+            sync_data.set_status(*status, *time);
+            request.sync_data.insert(id.to_string(), sync_data);
+        }
+        request
+    }
+
+    #[test]
+    fn test_share_status() -> Result<(), SyncError> {
+        let folder = tempfile::tempdir().unwrap();
+        let (server, url) = start(&folder);
+        let alice = client(&url, "alice");
+        let bob = client(&url, "bob");
+        let response = alice.set_data(&request(0, &[("1", Status::Treated, 1000)]))?;
+        let server_update_time = response["1"].server_update_time();
+        assert!(server_update_time > 0);
+        assert!(!response["1"].is_dirty());
+
+        // bob receives the change, then nothing since it
+        let response: SyncResponse = bob.set_data(&request(0, &[]))?;
+        assert_eq!(response["1"].status, Status::Treated);
+        assert!(bob.set_data(&request(server_update_time, &[]))?.is_empty());
+
+        // a change made before alice's one is not applied
+        let response =
+            bob.set_data(&request(server_update_time, &[("1", Status::Ignored, 900)]))?;
+        assert_eq!(response["1"].status, Status::Treated);
+
+        assert!(matches!(
+            client(&url, "mallory").set_data(&request(0, &[])),
+            Err(SyncError::Unauthorized)
+        ));
+        server.stop();
+        Ok(())
+    }
+
+    #[test]
+    fn test_share_notes() -> Result<(), SyncError> {
+        let folder = tempfile::tempdir().unwrap();
+        let (server, url) = start(&folder);
+        let alice = client(&url, "alice");
+        let response = alice.set_data(&request(0, &[("1", Status::Treated, 1000)]))?;
+        let server_update_time = response["1"].server_update_time();
+
+        // bob did not receive alice's status, his note is kept anyway
+        let mut bob_request = request(0, &[]);
+        let mut sync_data = SyncData::default();
+        sync_data.add_note(Note::new("bob", 1100, "segment 42 closed"));
+        bob_request.sync_data.insert("1".to_string(), sync_data);
+        let response = client(&url, "bob").set_data(&bob_request)?;
+        assert_eq!(response["1"].status, Status::Treated);
+        assert_eq!(response["1"].notes().len(), 1);
+        assert!(response["1"].server_update_time() > server_update_time);
+        server.stop();
+        Ok(())
+    }
+
+    #[test]
+    fn test_percent_encoded_source() -> Result<(), SyncError> {
+        let folder = tempfile::tempdir().unwrap();
+        let (server, url) = start(&folder);
+        let headers = [(
+            "Authorization".to_string(),
+            format!("Basic {}", STANDARD.encode("alice:secret")),
+        )];
+        let changes = request(0, &[("1", Status::Treated, 1000)]).sync_data;
+        let response = ReqwestTransport::default().post(
+            &format!("{url}/setData/team/France-Besan%C3%A7on?since=0"),
+            &headers,
+            serde_json::to_vec(&changes)?,
+        )?;
+        assert_eq!(response.status, 200);
+        assert!(
+            folder
+                .path()
+                .join("team")
+                .join("France-Besançon.json")
+                .exists()
+        );
+        server.stop();
+        Ok(())
+    }
+
+    #[test]
+    fn test_token() -> Result<(), SyncError> {
+        let folder = tempfile::tempdir().unwrap();
+        let (server, url) = start(&folder);
+        let token = client(&url, "alice").login()?;
+        let config = SyncConfig {
+            url: url.clone(),
+            team: "team".to_string(),
+            token: Some(token),
+            ..SyncConfig::default()
+        };
+        let alice = SyncClient::new(config.clone(), ReqwestTransport::default());
+        assert!(alice.set_data(&request(0, &[]))?.is_empty());
+        let other_team = SyncClient::new(
+            SyncConfig {
+                team: "other".to_string(),
+                ..config
+            },
+            ReqwestTransport::default(),
+        );
+        assert!(matches!(
+            other_team.set_data(&request(0, &[])),
+            Err(SyncError::Unauthorized)
+        ));
+        server.stop();
+        Ok(())
+    }
+}
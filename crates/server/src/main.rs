@@ -0,0 +1,20 @@
+use roadwork_server::SyncServer;
+use std::path::PathBuf;
+
+/// Usage: roadwork-server [address] [folder]
+fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let mut args = std::env::args().skip(1);
+    let address = args.next().unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    let folder = PathBuf::from(args.next().unwrap_or_else(|| "roadwork-server".to_string()));
+    match SyncServer::new(&address, folder.clone()) {
+        Ok(server) => {
+            println!("Listening on {address}, the data is kept in {folder:?}");
+            server.run();
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+}
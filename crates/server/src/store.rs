@@ -0,0 +1,77 @@
+use crate::ServerError;
+use roadwork_sync::SyncData;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// The sync data of a service for a team, by roadwork id
+pub type TeamData = HashMap<String, SyncData>;
+
+/// Keep the sync data in one json file per team and service:
+/// `{folder}/{team}/{source}.json`.
+/// The members of the teams are read from `{folder}/users.json`:
+/// `{"team": {"login": "password"}}`
+pub struct FileStore {
+    folder: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(folder: PathBuf) -> Self {
+        Self { folder }
+    }
+
+    /// The team and service names are used as file names, they can't go out of the folder
+    fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    }
+
+    fn get_path(&self, team: &str, source: &str) -> Result<PathBuf, ServerError> {
+        if !Self::is_valid_name(team) || !Self::is_valid_name(source) {
+            return Err(ServerError::InvalidName(format!("{team}/{source}")));
+        }
+        Ok(self.folder.join(team).join(format!("{source}.json")))
+    }
+
+    /// Returns true if the login is a member of the team with this password
+    pub fn is_member(&self, team: &str, login: &str, password: &str) -> Result<bool, ServerError> {
+        let users: HashMap<String, HashMap<String, String>> =
+            Self::read(&self.folder.join("users.json"))?.unwrap_or_default();
+        Ok(users
+            .get(team)
+            .and_then(|members| members.get(login))
+            .is_some_and(|expected| expected == password))
+    }
+
+    pub fn load(&self, team: &str, source: &str) -> Result<TeamData, ServerError> {
+        Ok(Self::read(&self.get_path(team, source)?)?.unwrap_or_default())
+    }
+
+    /// The file is replaced atomically so a crash never leaves a truncated file
+    pub fn save(&self, team: &str, source: &str, data: &TeamData) -> Result<(), ServerError> {
+        let path = self.get_path(team, source)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = path.with_extension("json.tmp");
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        serde_json::to_writer(&mut writer, data)?;
+        writer.flush()?;
+        drop(writer);
+        fs::rename(temp_path, path)?;
+        Ok(())
+    }
+
+    fn read<T: serde::de::DeserializeOwned>(path: &Path) -> Result<Option<T>, ServerError> {
+        match File::open(path) {
+            Ok(file) => Ok(Some(serde_json::from_reader(BufReader::new(file))?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,423 @@
+//! The client of the synchronization protocol of the Roadwork server.
+//!
+//! The local changes of a service are posted to `{url}/setData/{team}/{source}?since={time}`
+//! with a basic authentication, the body is the sync data of the changed roadworks by id.
+//! The server answers with the sync data of the roadworks changed after `since`, `since` being
+//! the most recent server update time already received. The team and the source are percent-encoded.
+//!
+//! The requests are authenticated with the login and password, or with a token: [`SyncClient::login`]
+//! posts the login and password to `{url}/login/{team}` and returns the token the server gives.
+//!
+//! The http requests are made by a [`Transport`], so the client can use the http client of the
+//! application, or a mock in the tests:
+//!
+//! ```
+//! use roadwork_sync::{SyncClient, SyncConfig, SyncError, SyncRequest, Transport, TransportResponse};
+//!
+//! struct Offline;
+//!
+//! impl Transport for Offline {
+//!     fn post(
+//!         &self,
+//!         _url: &str,
+//!         _headers: &[(String, String)],
+//!         _body: Vec<u8>,
+//!     ) -> Result<TransportResponse, SyncError> {
+//!         Ok(TransportResponse { status: 200, body: b"{}".to_vec() })
+//!     }
+//! }
+//!
+//! let config = SyncConfig {
+//!     url: "http://localhost:8080".to_string(),
+//!     team: "my-team".to_string(),
+//!     login: "login".to_string(),
+//!     password: "password".to_string(),
+//!     token: None,
+//! };
+//! let client = SyncClient::new(config, Offline);
+//! let response = client.set_data(&SyncRequest::new("France-Paris", 0)).unwrap();
+//! assert!(response.is_empty());
+//! ```
+
+use crate::SyncData;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// The sync data of the roadworks changed on the server, by roadwork id
+pub type SyncResponse = HashMap<String, SyncData>;
+
+#[derive(Error, Debug)]
+pub enum SyncError {
+    #[error("The synchronization is not configured: {0}")]
+    Configuration(String),
+    #[error("Unable to reach the server: {0}")]
+    Transport(String),
+    #[error("The login or the password is wrong")]
+    Unauthorized,
+    #[error("The server answered {status}: {body}")]
+    Status { status: u16, body: String },
+    #[error("Invalid json {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Where and as who the statuses are synchronized
+#[derive(Debug, Clone, Default)]
+pub struct SyncConfig {
+    pub url: String,
+    pub team: String,
+    pub login: String,
+    pub password: String,
+    /// The token given by the server, it is used instead of the login and password when defined
+    pub token: Option<String>,
+}
+
+/// The local changes of a service
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SyncRequest {
+    pub source: String,
+    /// The most recent server update time already received
+    #[serde(default)]
+    pub since: u64,
+    /// The sync data of the roadworks whose status was changed locally, by roadwork id
+    #[serde(rename = "syncData")]
+    pub sync_data: HashMap<String, SyncData>,
+}
+
+impl SyncRequest {
+    /// A request without local change, it only receives the changes of the team
+    pub fn new(source: &str, since: u64) -> Self {
+        Self {
+            source: source.to_string(),
+            since,
+            sync_data: HashMap::new(),
+        }
+    }
+}
+
+/// The answer of the server to a login
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LoginResponse {
+    pub token: String,
+}
+
+/// The answer of the server to a post
+#[derive(Debug, Clone)]
+pub struct TransportResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+/// Send the http requests of the client
+pub trait Transport {
+    /// Post the body with the headers, the response is returned whatever its status
+    fn post(
+        &self,
+        url: &str,
+        headers: &[(String, String)],
+        body: Vec<u8>,
+    ) -> Result<TransportResponse, SyncError>;
+}
+
+impl<T: Transport + ?Sized> Transport for &T {
+    fn post(
+        &self,
+        url: &str,
+        headers: &[(String, String)],
+        body: Vec<u8>,
+    ) -> Result<TransportResponse, SyncError> {
+        (**self).post(url, headers, body)
+    }
+}
+
+pub struct SyncClient<T: Transport> {
+    config: SyncConfig,
+    transport: T,
+}
+
+impl<T: Transport> SyncClient<T> {
+    pub fn new(config: SyncConfig, transport: T) -> Self {
+        Self { config, transport }
+    }
+
+    /// Returns the url the changes of the service are posted to
+    pub fn set_data_url(&self, source: &str, since: u64) -> Result<String, SyncError> {
+        let url = self.check_config()?;
+        Ok(format!(
+            "{url}/setData/{}/{}?since={since}",
+            urlencoding::encode(&self.config.team),
+            urlencoding::encode(source)
+        ))
+    }
+
+    fn check_config(&self) -> Result<&str, SyncError> {
+        let url = self.config.url.trim_end_matches('/');
+        if url.is_empty() {
+            return Err(SyncError::Configuration(
+                "the server url is not defined".to_string(),
+            ));
+        }
+        if self.config.team.is_empty() {
+            return Err(SyncError::Configuration(
+                "the team is not defined".to_string(),
+            ));
+        }
+        Ok(url)
+    }
+
+    /// Returns the value of the Authorization header, the token if there is one
+    pub fn authorization(&self) -> String {
+        match &self.config.token {
+            Some(token) => format!("Bearer {token}"),
+            None => self.basic_authorization(),
+        }
+    }
+
+    fn basic_authorization(&self) -> String {
+        let credentials = format!("{}:{}", self.config.login, self.config.password);
+        format!("Basic {}", STANDARD.encode(credentials))
+    }
+
+    /// Exchange the login and password for a token.
+    /// The servers that don't give tokens answer with a 404 status
+    pub fn login(&self) -> Result<String, SyncError> {
+        let url = format!(
+            "{}/login/{}",
+            self.check_config()?,
+            urlencoding::encode(&self.config.team)
+        );
+        let headers = [("Authorization".to_string(), self.basic_authorization())];
+        let response = Self::check_status(self.transport.post(&url, &headers, Vec::new())?)?;
+        let login: LoginResponse = serde_json::from_slice(&response.body)?;
+        Ok(login.token)
+    }
+
+    fn check_status(response: TransportResponse) -> Result<TransportResponse, SyncError> {
+        match response.status {
+            200..=299 => Ok(response),
+            401 | 403 => Err(SyncError::Unauthorized),
+            status => Err(SyncError::Status {
+                status,
+                body: String::from_utf8_lossy(&response.body).into_owned(),
+            }),
+        }
+    }
+
+    /// Send the local changes, returns the roadworks changed on the server since the request time
+    pub fn set_data(&self, request: &SyncRequest) -> Result<SyncResponse, SyncError> {
+        let url = self.set_data_url(&request.source, request.since)?;
+        let headers = [
+            ("Authorization".to_string(), self.authorization()),
+            ("Content-Type".to_string(), "application/json".to_string()),
+        ];
+        let body = serde_json::to_vec(&Self::wire_sync_data(&request.sync_data)?)?;
+        let response = Self::check_status(self.transport.post(&url, &headers, body)?)?;
+        Ok(serde_json::from_slice(&response.body)?)
+    }
+
+    /// The sync data as sent to the server, without the bookkeeping of the notes to send
+    fn wire_sync_data(sync_data: &HashMap<String, SyncData>) -> Result<Value, SyncError> {
+        let mut value = serde_json::to_value(sync_data)?;
+        if let Some(roadworks) = value.as_object_mut() {
+            for roadwork in roadworks.values_mut() {
+                if let Some(fields) = roadwork.as_object_mut() {
+                    fields.remove("notesDirty");
+                }
+            }
+        }
+        Ok(value)
+    }
+}
+
+/// A transport made of a blocking reqwest client
+#[cfg(feature = "reqwest")]
+#[derive(Default)]
+pub struct ReqwestTransport {
+    client: reqwest::blocking::Client,
+}
+
+#[cfg(feature = "reqwest")]
+impl ReqwestTransport {
+    pub fn new(client: reqwest::blocking::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl Transport for ReqwestTransport {
+    fn post(
+        &self,
+        url: &str,
+        headers: &[(String, String)],
+        body: Vec<u8>,
+    ) -> Result<TransportResponse, SyncError> {
+        let mut request_builder = self.client.post(url).body(body);
+        for (name, value) in headers {
+            request_builder = request_builder.header(name, value);
+        }
+        let response = request_builder
+            .send()
+            .map_err(|e| SyncError::Transport(e.to_string()))?;
+        let status = response.status().as_u16();
+        let body = response
+            .bytes()
+            .map_err(|e| SyncError::Transport(e.to_string()))?;
+        Ok(TransportResponse {
+            status,
+            body: body.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Status;
+    use std::cell::RefCell;
+
+    /// The url, the headers and the body of a request
+    type Posted = (String, Vec<(String, String)>, Vec<u8>);
+
+    /// Record the requests and answer with the given response
+    struct MockTransport {
+        response: TransportResponse,
+        requests: RefCell<Vec<Posted>>,
+    }
+
+    impl MockTransport {
+        fn new(status: u16, body: &str) -> Self {
+            Self {
+                response: TransportResponse {
+                    status,
+                    body: body.as_bytes().to_vec(),
+                },
+                requests: RefCell::new(Vec::new()),
+            }
+        }
+    }
+
+    impl Transport for MockTransport {
+        fn post(
+            &self,
+            url: &str,
+            headers: &[(String, String)],
+            body: Vec<u8>,
+        ) -> Result<TransportResponse, SyncError> {
+            self.requests
+                .borrow_mut()
+                .push((url.to_string(), headers.to_vec(), body));
+            Ok(self.response.clone())
+        }
+    }
+
+    fn config() -> SyncConfig {
+        SyncConfig {
+            url: "http://localhost:8080/".to_string(),
+            team: "team".to_string(),
+            login: "login".to_string(),
+            password: "password".to_string(),
+            token: None,
+        }
+    }
+
+    #[test]
+    fn test_set_data() {
+        let transport = MockTransport::new(
+            200,
+            r#"{"2": {"localUpdateTime": 5, "serverUpdateTime": 6, "status": "Ignored", "dirty": false}}"#,
+        );
+        let client = SyncClient::new(config(), &transport);
+        let mut request = SyncRequest::new("France-Paris", 4);
+        let mut treated = SyncData::default();
+        treated.set_status(Status::Treated, 3);
+        treated.add_note(crate::Note::new("login", 3, "closed"));
+        request.sync_data.insert("1".to_string(), treated);
+
+        let response = client.set_data(&request).unwrap();
+        assert_eq!(response["2"].status, Status::Ignored);
+        let requests = transport.requests.borrow();
+        let (url, headers, body) = &requests[0];
+        assert_eq!(
+            url,
+            "http://localhost:8080/setData/team/France-Paris?since=4"
+        );
+        assert!(headers.contains(&(
+            "Authorization".to_string(),
+            "Basic bG9naW46cGFzc3dvcmQ=".to_string()
+        )));
+        let sent: SyncResponse = serde_json::from_slice(body).unwrap();
+        assert_eq!(sent["1"].status, Status::Treated);
+        assert_eq!(sent["1"].notes().len(), 1);
+        // the notes to send are only known by the client
+        let sent: Value = serde_json::from_slice(body).unwrap();
+        assert!(sent["1"].get("notesDirty").is_none());
+    }
+
+    #[test]
+    fn test_login() {
+        let transport = MockTransport::new(200, r#"{"token": "abc"}"#);
+        let client = SyncClient::new(config(), &transport);
+        assert_eq!(client.login().unwrap(), "abc");
+        assert_eq!(
+            transport.requests.borrow()[0].0,
+            "http://localhost:8080/login/team"
+        );
+        let config = SyncConfig {
+            token: Some("abc".to_string()),
+            ..config()
+        };
+        let client = SyncClient::new(config, &transport);
+        assert_eq!(client.authorization(), "Bearer abc");
+    }
+
+    #[test]
+    fn test_encoded_url() {
+        let transport = MockTransport::new(200, r#"{"token": "abc"}"#);
+        let config = SyncConfig {
+            team: "équipe nord".to_string(),
+            ..config()
+        };
+        let client = SyncClient::new(config, &transport);
+        client
+            .set_data(&SyncRequest::new("France-Besançon/Doubs", 0))
+            .ok();
+        client.login().unwrap();
+        let requests = transport.requests.borrow();
+        assert_eq!(
+            requests[0].0,
+            "http://localhost:8080/setData/%C3%A9quipe%20nord/France-Besan%C3%A7on%2FDoubs?since=0"
+        );
+        assert_eq!(
+            requests[1].0,
+            "http://localhost:8080/login/%C3%A9quipe%20nord"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let request = SyncRequest::new("France-Paris", 0);
+        let client = SyncClient::new(config(), MockTransport::new(401, ""));
+        assert!(matches!(
+            client.set_data(&request),
+            Err(SyncError::Unauthorized)
+        ));
+        let client = SyncClient::new(config(), MockTransport::new(500, "down"));
+        assert!(matches!(
+            client.set_data(&request),
+            Err(SyncError::Status { status: 500, .. })
+        ));
+        let client = SyncClient::new(config(), MockTransport::new(404, ""));
+        assert!(matches!(
+            client.login(),
+            Err(SyncError::Status { status: 404, .. })
+        ));
+        let client = SyncClient::new(SyncConfig::default(), MockTransport::new(200, "{}"));
+        assert!(matches!(
+            client.set_data(&request),
+            Err(SyncError::Configuration(_))
+        ));
+    }
+}
@@ -1,3 +1,14 @@
+//! The synchronization of the roadwork statuses within a team: the sync data of a roadwork with
+//! the notes of the team, the resolution of the conflicts, and the client of the server.
+
+pub mod client;
+
+#[cfg(feature = "reqwest")]
+pub use client::ReqwestTransport;
+pub use client::{
+    LoginResponse, SyncClient, SyncConfig, SyncError, SyncRequest, SyncResponse, Transport,
+    TransportResponse,
+};
 use serde::{Deserialize, Serialize};
 use strum_macros::{Display, IntoStaticStr};
 
@@ -11,29 +22,206 @@ pub struct SyncData {
     #[serde(rename = "serverUpdateTime")]
     pub(crate) server_update_time: u64,
     pub status: Status,
+    /**
+     * The login of the member of the team in charge of the roadwork
+     */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) assignee: Option<String>,
     pub(crate) dirty: bool,
+    /**
+     * The comments of the team, in chronological order
+     */
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) notes: Vec<Note>,
+    /**
+     * Some notes were not received by the server yet.
+     * It is only kept in the local caches, the client removes it from the requests
+     */
+    #[serde(
+        rename = "notesDirty",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub(crate) notes_dirty: bool,
 }
 
 impl SyncData {
     pub fn new_from(src: &SyncData) -> Self {
         Self {
             dirty: false,
+            notes_dirty: false,
             ..src.clone()
         }
     }
 
+    /**
+     * Take the state sent by the server, the local change was received so it is not dirty anymore.
+     * The notes are merged, the local ones the server does not have yet are still to be sent
+     */
     pub fn copy(&mut self, other: &SyncData) {
         self.local_update_time = other.local_update_time;
         self.server_update_time = other.server_update_time;
         self.status = other.status;
+        self.assignee = other.assignee.clone();
+        self.dirty = false;
+        self.notes_dirty = self.notes.iter().any(|note| !other.notes.contains(note));
+        self.merge_notes(&other.notes);
+    }
+
+    /**
+     * Change the status locally, it will be sent at the next synchronization
+     */
+    pub fn set_status(&mut self, status: Status, local_update_time: u64) {
+        self.status = status;
+        self.local_update_time = local_update_time;
+        self.dirty = true;
+    }
+
+    /**
+     * Assign the roadwork locally, it will be sent at the next synchronization like a status
+     */
+    pub fn set_assignee(&mut self, assignee: Option<String>, local_update_time: u64) {
+        self.assignee = assignee;
+        self.local_update_time = local_update_time;
+        self.dirty = true;
+    }
+
+    pub fn assignee(&self) -> Option<&str> {
+        self.assignee.as_deref()
+    }
+
+    /**
+     * Take the state sent by the server unless it conflicts with a local change.
+     * There is a conflict when the status or the assignee was changed locally while another
+     * member of the team changed it to something else on the server.
+     *
+     * @return true if the conflict is left to the user
+     */
+    pub fn resolve(&mut self, server: &SyncData, policy: ConflictPolicy) -> bool {
+        if !self.is_conflict(server) {
+            self.copy(server);
+            return false;
+        }
+        match policy {
+            ConflictPolicy::ServerWins => self.copy(server),
+            ConflictPolicy::LatestWins if server.local_update_time >= self.local_update_time => {
+                self.copy(server)
+            }
+            ConflictPolicy::LatestWins => self.keep_local(server),
+            ConflictPolicy::Ask => return true,
+        }
+        false
+    }
+
+    /**
+     * Add a note locally, it will be sent at the next synchronization.
+     * The local update time is the one of the status, a note is not a status change
+     */
+    pub fn add_note(&mut self, note: Note) {
+        self.notes.push(note);
+        self.notes_dirty = true;
+    }
+
+    /**
+     * Add the notes that are not known yet, a note is never removed
+     */
+    pub fn merge_notes(&mut self, notes: &[Note]) {
+        for note in notes {
+            if !self.notes.contains(note) {
+                self.notes.push(note.clone());
+            }
+        }
+        self.notes.sort_by_key(|note| note.time);
+    }
+
+    pub fn notes(&self) -> &[Note] {
+        &self.notes
+    }
+
+    fn is_conflict(&self, server: &SyncData) -> bool {
+        self.dirty
+            && (server.status != self.status || server.assignee != self.assignee)
+            && server.server_update_time > self.server_update_time
+    }
+
+    /**
+     * Keep the local status over the server one, it is sent again at the next synchronization
+     */
+    pub fn keep_local(&mut self, server: &SyncData) {
+        self.server_update_time = server.server_update_time;
+        self.dirty = true;
+        self.merge_notes(&server.notes);
     }
 
+    /**
+     * Record the change on the server, the local update time of the member who made it is kept
+     */
+    pub fn received(&mut self, server_update_time: u64) {
+        self.server_update_time = server_update_time;
+        self.dirty = false;
+        self.notes_dirty = false;
+    }
+
+    /// The status or some notes were changed locally and not sent yet
+    pub fn is_dirty(&self) -> bool {
+        self.dirty || self.notes_dirty
+    }
+
+    pub fn local_update_time(&self) -> u64 {
+        self.local_update_time
+    }
+
+    pub fn server_update_time(&self) -> u64 {
+        self.server_update_time
+    }
+
+    #[cfg(test)]
     pub(crate) fn update_time(&mut self, server_update_time: u64) {
         self.local_update_time = server_update_time;
         self.server_update_time = server_update_time;
     }
 }
 
+/// A comment of a member of the team on a roadwork, for example why it was ignored
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Note {
+    pub author: String,
+    /// The creation time in milliseconds
+    pub time: u64,
+    pub text: String,
+}
+
+impl Note {
+    pub fn new(author: &str, time: u64, text: &str) -> Self {
+        Self {
+            author: author.to_string(),
+            time,
+            text: text.to_string(),
+        }
+    }
+}
+
+/// How a status changed both locally and by another member of the team is resolved
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictPolicy {
+    /// The server status replaces the local one
+    #[default]
+    ServerWins,
+    /// The most recent change is kept
+    LatestWins,
+    /// The user chooses
+    Ask,
+}
+
+/// A roadwork whose status or assignee was changed both locally and on the server
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub id: String,
+    pub local: SyncData,
+    pub server: SyncData,
+}
+
 #[derive(
     Debug,
     Default,
@@ -56,3 +244,107 @@ pub enum Status {
     Finished,
     Treated,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A roadwork received at 1000, changed locally at 2000 and by another member at 3000
+    fn conflict() -> (SyncData, SyncData) {
+        let mut local = SyncData::default();
+        local.update_time(1000);
+        local.set_status(Status::Treated, 2000);
+        let mut server = SyncData {
+            status: Status::Ignored,
+            ..SyncData::default()
+        };
+        server.update_time(3000);
+        (local, server)
+    }
+
+    #[test]
+    fn test_no_conflict() {
+        let (mut local, mut server) = conflict();
+        server.status = Status::Treated;
+        assert!(!local.resolve(&server, ConflictPolicy::Ask));
+        assert!(!local.is_dirty());
+        assert_eq!(local.server_update_time(), 3000);
+    }
+
+    #[test]
+    fn test_policies() {
+        let (mut local, server) = conflict();
+        assert!(!local.resolve(&server, ConflictPolicy::ServerWins));
+        assert_eq!(local.status, Status::Ignored);
+
+        let (mut local, server) = conflict();
+        assert!(!local.resolve(&server, ConflictPolicy::LatestWins));
+        assert_eq!(local.status, Status::Ignored);
+
+        let (mut local, server) = conflict();
+        local.set_status(Status::Later, 4000);
+        assert!(!local.resolve(&server, ConflictPolicy::LatestWins));
+        assert_eq!(local.status, Status::Later);
+        assert!(local.is_dirty());
+        assert_eq!(local.server_update_time(), 3000);
+
+        let (mut local, server) = conflict();
+        assert!(local.resolve(&server, ConflictPolicy::Ask));
+        assert_eq!(local.status, Status::Treated);
+        assert!(local.is_dirty());
+    }
+
+    #[test]
+    fn test_assignee() {
+        let mut local = SyncData::default();
+        local.update_time(1000);
+        local.set_assignee(Some("alice".to_string()), 2000);
+        assert!(local.is_dirty());
+
+        // bob took the roadwork in the meantime
+        let mut server = SyncData::default();
+        server.set_assignee(Some("bob".to_string()), 1500);
+        server.received(3000);
+        assert!(local.resolve(&server, ConflictPolicy::Ask));
+        assert!(!local.resolve(&server, ConflictPolicy::ServerWins));
+        assert_eq!(local.assignee(), Some("bob"));
+        assert!(!local.is_dirty());
+    }
+
+    #[test]
+    fn test_notes() {
+        let mut local = SyncData::default();
+        local.update_time(1000);
+        local.add_note(Note::new("alice", 2000, "segment 42 closed"));
+        assert!(local.is_dirty());
+
+        // a note alone is not a status conflict
+        let mut server = SyncData {
+            status: Status::Ignored,
+            ..SyncData::default()
+        };
+        server.update_time(3000);
+        server.add_note(Note::new("bob", 1500, "not on the map"));
+        assert!(!local.resolve(&server, ConflictPolicy::Ask));
+        assert_eq!(local.status, Status::Ignored);
+        let texts: Vec<&str> = local
+            .notes()
+            .iter()
+            .map(|note| note.text.as_str())
+            .collect();
+        assert_eq!(texts, ["not on the map", "segment 42 closed"]);
+        // the server does not have alice's note yet
+        assert!(local.is_dirty());
+
+        // the flag is kept in the local caches while there are notes to send
+        let cached = serde_json::to_value(&local).unwrap();
+        assert_eq!(cached["notesDirty"], true);
+
+        server.merge_notes(local.notes());
+        local.copy(&server);
+        assert!(!local.is_dirty());
+        assert_eq!(local.notes().len(), 2);
+        let cached = serde_json::to_value(&local).unwrap();
+        assert!(cached.get("notesDirty").is_none());
+    }
+}
@@ -0,0 +1,201 @@
+use crate::MyError;
+use argon2::Argon2;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// The environment variable holding the passphrase of the credentials file
+pub(crate) const PASSPHRASE_VARIABLE: &str = "ROADWORK_PASSPHRASE";
+
+/// The secrets of the synchronization, kept out of the settings file
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct Credentials {
+    pub(crate) password: String,
+    /// The token given by the server in exchange of the login and password
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) token: Option<String>,
+}
+
+/// The content of the credentials file
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "format", rename_all = "camelCase")]
+enum CredentialsFile {
+    Plain {
+        credentials: Credentials,
+    },
+    /// The credentials are encrypted with ChaCha20-Poly1305, the key is derived from the
+    /// passphrase with Argon2
+    Encrypted {
+        salt: String,
+        nonce: String,
+        data: String,
+    },
+}
+
+/// Keep the credentials in a file only readable by the user,
+/// encrypted when a passphrase is given
+pub(crate) struct CredentialsStore {
+    path: PathBuf,
+}
+
+impl CredentialsStore {
+    pub(crate) fn new(folder: &Path) -> Self {
+        Self {
+            path: folder.join("credentials.json"),
+        }
+    }
+
+    /// Returns the passphrase defined in the environment
+    pub(crate) fn passphrase() -> Option<String> {
+        std::env::var(PASSPHRASE_VARIABLE)
+            .ok()
+            .filter(|passphrase| !passphrase.is_empty())
+    }
+
+    /// Read the credentials, the default ones if the file does not exist
+    pub(crate) fn load(&self, passphrase: Option<&str>) -> Result<Credentials, MyError> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Credentials::default()),
+            Err(e) => return Err(e.into()),
+        };
+        match serde_json::from_reader(BufReader::new(file))? {
+            CredentialsFile::Plain { credentials } => Ok(credentials),
+            CredentialsFile::Encrypted { salt, nonce, data } => {
+                let passphrase = passphrase.ok_or_else(|| {
+                    MyError::CredentialsError(format!(
+                        "{:?} is encrypted, define its passphrase in {PASSPHRASE_VARIABLE}",
+                        self.path
+                    ))
+                })?;
+                let cipher = Self::cipher(passphrase, &Self::decode(&salt)?)?;
+                let data = cipher
+                    .decrypt(
+                        Nonce::from_slice(&Self::decode(&nonce)?),
+                        Self::decode(&data)?.as_ref(),
+                    )
+                    .map_err(|_| {
+                        MyError::CredentialsError(format!(
+                            "Unable to decrypt {:?}, the passphrase is wrong",
+                            self.path
+                        ))
+                    })?;
+                Ok(serde_json::from_slice(&data)?)
+            }
+        }
+    }
+
+    /// Write the credentials, encrypted if there is a passphrase
+    pub(crate) fn save(
+        &self,
+        credentials: &Credentials,
+        passphrase: Option<&str>,
+    ) -> Result<(), MyError> {
+        info!("save credentials to {:?}", self.path);
+        let content = match passphrase {
+            None => CredentialsFile::Plain {
+                credentials: credentials.clone(),
+            },
+            Some(passphrase) => {
+                let mut salt = [0u8; 16];
+                OsRng.fill_bytes(&mut salt);
+                let cipher = Self::cipher(passphrase, &salt)?;
+                let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+                let data = cipher
+                    .encrypt(&nonce, serde_json::to_vec(credentials)?.as_ref())
+                    .map_err(|e| MyError::CredentialsError(e.to_string()))?;
+                CredentialsFile::Encrypted {
+                    salt: BASE64_STANDARD.encode(salt),
+                    nonce: BASE64_STANDARD.encode(nonce),
+                    data: BASE64_STANDARD.encode(data),
+                }
+            }
+        };
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = Self::create_private(&self.path)?;
+        serde_json::to_writer_pretty(&mut file, &content)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Create the file, only its owner can read it
+    #[cfg(unix)]
+    fn create_private(path: &Path) -> Result<File, MyError> {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        // the mode is only applied when the file is created
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        Ok(file)
+    }
+
+    #[cfg(not(unix))]
+    fn create_private(path: &Path) -> Result<File, MyError> {
+        Ok(OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?)
+    }
+
+    fn cipher(passphrase: &str, salt: &[u8]) -> Result<ChaCha20Poly1305, MyError> {
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| MyError::CredentialsError(e.to_string()))?;
+        Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+
+    fn decode(value: &str) -> Result<Vec<u8>, MyError> {
+        BASE64_STANDARD
+            .decode(value)
+            .map_err(|e| MyError::CredentialsError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypted_credentials() -> Result<(), MyError> {
+        let folder = tempfile::tempdir()?;
+        let store = CredentialsStore::new(folder.path());
+        assert_eq!(store.load(None)?, Credentials::default());
+        let credentials = Credentials {
+            password: "secret".to_string(),
+            token: Some("token".to_string()),
+        };
+        store.save(&credentials, Some("passphrase"))?;
+        let content = fs::read_to_string(folder.path().join("credentials.json"))?;
+        assert!(!content.contains("secret"));
+        assert_eq!(store.load(Some("passphrase"))?, credentials);
+        assert!(store.load(Some("wrong")).is_err());
+        assert!(store.load(None).is_err());
+
+        store.save(&credentials, None)?;
+        assert_eq!(store.load(None)?, credentials);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = fs::metadata(folder.path().join("credentials.json"))?;
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use crate::model::roadwork::Roadwork;
+use egui::{Button, RichText, Ui};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Who is in charge of the roadwork, the user can take it or release it
+pub(crate) struct AssigneePanel<'a> {
+    roadwork: &'a mut Roadwork,
+    /// The synchronization login of the user
+    login: &'a str,
+}
+
+impl<'a> AssigneePanel<'a> {
+    pub(crate) fn new(roadwork: &'a mut Roadwork, login: &'a str) -> Self {
+        Self { roadwork, login }
+    }
+
+    /// Returns true if the user changed the assignee
+    pub(crate) fn show(self, ui: &mut Ui) -> bool {
+        let assignee = self.roadwork.sync_data.assignee().map(str::to_string);
+        let mut new_assignee = assignee.clone();
+        ui.horizontal(|ui| {
+            ui.label(RichText::new("Assigned to:").strong());
+            ui.label(assignee.as_deref().unwrap_or("nobody"));
+        });
+        ui.horizontal(|ui| {
+            let mine = assignee.as_deref() == Some(self.login);
+            if ui
+                .add_enabled(!self.login.is_empty() && !mine, Button::new("Assign to me"))
+                .on_disabled_hover_text("The synchronization login is the assignee")
+                .clicked()
+            {
+                new_assignee = Some(self.login.to_string());
+            }
+            if ui
+                .add_enabled(assignee.is_some(), Button::new("Unassign"))
+                .clicked()
+            {
+                new_assignee = None;
+            }
+        });
+        if new_assignee == assignee {
+            return false;
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        self.roadwork
+            .sync_data
+            .set_assignee(new_assignee, now.as_millis() as u64);
+        self.roadwork.changed_by_team = false;
+        true
+    }
+}
@@ -0,0 +1,88 @@
+use crate::model::roadwork::format_date;
+use egui::{Context, RichText};
+use roadwork_sync::{Conflict, SyncData};
+
+#[derive(Clone, Copy)]
+pub(crate) enum ConflictAction {
+    /// Keep the local status of the conflict at this index, it is sent again
+    KeepLocal(usize),
+    /// Take the status set by the team for the conflict at this index
+    TakeServer(usize),
+    /// Select the roadwork of the conflict at this index
+    Show(usize),
+}
+
+/// Shows the roadworks whose status was changed both locally and by another member of the team
+pub(crate) struct ConflictsDialog<'a> {
+    open: &'a mut bool,
+    /// The conflicts with the service of their roadwork
+    conflicts: &'a [(String, Conflict)],
+}
+
+impl<'a> ConflictsDialog<'a> {
+    pub(crate) fn new(open: &'a mut bool, conflicts: &'a [(String, Conflict)]) -> Self {
+        Self { open, conflicts }
+    }
+
+    fn describe(sync_data: &SyncData, time: u64) -> String {
+        match sync_data.assignee() {
+            Some(assignee) => format!(
+                "{}, assigned to {assignee} ({})",
+                sync_data.status,
+                format_date(time as i64)
+            ),
+            None => format!("{} ({})", sync_data.status, format_date(time as i64)),
+        }
+    }
+
+    pub(crate) fn show(&mut self, ctx: &Context) -> Option<ConflictAction> {
+        let screen = ctx.content_rect().size();
+        let max = egui::vec2(screen.x * 0.9, screen.y * 0.9);
+        let mut action = None;
+        egui::Window::new("Synchronization conflicts")
+            .open(self.open)
+            .resizable(true)
+            .max_size(max)
+            .show(ctx, |ui| {
+                if self.conflicts.is_empty() {
+                    ui.label("No conflict");
+                    return;
+                }
+                egui::ScrollArea::both().show(ui, |ui| {
+                    egui::Grid::new("conflicts_grid")
+                        .num_columns(7)
+                        .spacing([8.0, 4.0])
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for title in ["Service", "Id", "Mine", "Team"] {
+                                ui.label(RichText::new(title).strong());
+                            }
+                            ui.end_row();
+                            for (index, (source, conflict)) in self.conflicts.iter().enumerate() {
+                                ui.label(source);
+                                ui.label(&conflict.id);
+                                ui.label(Self::describe(
+                                    &conflict.local,
+                                    conflict.local.local_update_time(),
+                                ));
+                                ui.label(Self::describe(
+                                    &conflict.server,
+                                    conflict.server.server_update_time(),
+                                ));
+                                if ui.button("Keep mine").clicked() {
+                                    action = Some(ConflictAction::KeepLocal(index));
+                                }
+                                if ui.button("Take theirs").clicked() {
+                                    action = Some(ConflictAction::TakeServer(index));
+                                }
+                                if ui.button("Show").clicked() {
+                                    action = Some(ConflictAction::Show(index));
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+        action
+    }
+}
@@ -0,0 +1,107 @@
+use crate::model::parse_diagnostic::ParseDiagnostic;
+use crate::model::roadwork_data::RoadworkData;
+use eframe::epaint::text::TextWrapMode;
+use egui::{Context, Label, RichText, Ui};
+
+/// Shows the records that were rejected or partially parsed
+pub(crate) struct DiagnosticsPanel<'a> {
+    open: &'a mut bool,
+    roadwork_data: Option<&'a RoadworkData>,
+}
+
+impl<'a> DiagnosticsPanel<'a> {
+    pub(crate) fn new(open: &'a mut bool, roadwork_data: Option<&'a RoadworkData>) -> Self {
+        Self {
+            open,
+            roadwork_data,
+        }
+    }
+
+    pub(crate) fn show_button(&mut self, ctx: &Context, ui: &mut Ui) {
+        let count = self
+            .roadwork_data
+            .map(|roadwork_data| roadwork_data.diagnostics.len())
+            .unwrap_or_default();
+        if ui
+            .add_enabled(
+                count > 0,
+                egui::Button::new(format!("Diagnostics ({count})")),
+            )
+            .clicked()
+        {
+            *self.open = true;
+        }
+        if *self.open {
+            self.show(ctx);
+        }
+    }
+
+    pub(crate) fn show(&mut self, ctx: &Context) {
+        let screen = ctx.content_rect().size();
+        let max = egui::vec2(screen.x * 0.9, screen.y * 0.9);
+        let roadwork_data = self.roadwork_data;
+        egui::Window::new("Parse diagnostics")
+            .open(self.open)
+            .resizable(true)
+            .max_size(max)
+            .show(ctx, |ui| {
+                let Some(roadwork_data) = roadwork_data else {
+                    ui.label("No data loaded");
+                    return;
+                };
+                let rejected_count = roadwork_data.rejected_count();
+                ui.label(format!(
+                    "{rejected_count} of {} records rejected, {} fields not parsed",
+                    roadwork_data.roadworks.len() + rejected_count,
+                    roadwork_data.diagnostics.len() - rejected_count
+                ));
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for diagnostic in &roadwork_data.diagnostics {
+                        Self::show_diagnostic(ui, diagnostic);
+                    }
+                });
+            });
+    }
+
+    fn show_diagnostic(ui: &mut Ui, diagnostic: &ParseDiagnostic) {
+        let title = format!(
+            "#{} {} - {}: {}",
+            diagnostic.index,
+            diagnostic.record_id.as_deref().unwrap_or("?"),
+            diagnostic.field,
+            if diagnostic.rejected {
+                "rejected"
+            } else {
+                "not parsed"
+            }
+        );
+        egui::CollapsingHeader::new(title)
+            .id_salt((diagnostic.index, &diagnostic.field))
+            .show(ui, |ui| {
+                egui::Grid::new(("diagnostic_grid", diagnostic.index, &diagnostic.field))
+                    .num_columns(2)
+                    .spacing([6.0, 4.0])
+                    .show(ui, |ui| {
+                        Self::add_row(ui, "Field:", &diagnostic.field);
+                        if let Some(path) = &diagnostic.path {
+                            Self::add_row(ui, "Path:", path);
+                        }
+                        if let Some(raw_value) = &diagnostic.raw_value {
+                            Self::add_row(ui, "Raw value:", raw_value);
+                        }
+                        Self::add_row(ui, "Error:", &diagnostic.error);
+                    });
+                ui.label(RichText::new("Record:").strong());
+                ui.add(
+                    Label::new(RichText::new(&diagnostic.record).monospace())
+                        .wrap_mode(TextWrapMode::Wrap),
+                );
+            });
+    }
+
+    fn add_row(ui: &mut Ui, label: &str, value: &str) {
+        ui.label(RichText::new(label).strong());
+        ui.add(Label::new(value).wrap_mode(TextWrapMode::Wrap));
+        ui.end_row();
+    }
+}
@@ -0,0 +1,94 @@
+use crate::model::roadwork::format_date;
+use crate::model::roadwork_history::RoadworkHistory;
+use crate::opendata::json::model::lat_lng::LatLng;
+use egui::{Context, RichText};
+
+pub(crate) enum HistoryAction {
+    Export,
+    /// Center the map on the roadwork
+    Show(LatLng),
+}
+
+/// Shows the roadworks that disappeared from the opendata service
+pub(crate) struct HistoryDialog<'a> {
+    open: &'a mut bool,
+    history: &'a RoadworkHistory,
+}
+
+impl<'a> HistoryDialog<'a> {
+    pub(crate) fn new(open: &'a mut bool, history: &'a RoadworkHistory) -> Self {
+        Self { open, history }
+    }
+
+    pub(crate) fn show(&mut self, ctx: &Context) -> Option<HistoryAction> {
+        let screen = ctx.content_rect().size();
+        let max = egui::vec2(screen.x * 0.9, screen.y * 0.9);
+        let history = self.history;
+        let mut action = None;
+        egui::Window::new(format!("History of {}", history.source))
+            .open(self.open)
+            .resizable(true)
+            .max_size(max)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} removed roadworks", history.roadworks.len()));
+                    if ui
+                        .add_enabled(
+                            !history.roadworks.is_empty(),
+                            egui::Button::new("Export csv"),
+                        )
+                        .clicked()
+                    {
+                        action = Some(HistoryAction::Export);
+                    }
+                });
+                egui::ScrollArea::both().show(ui, |ui| {
+                    egui::Grid::new("history_grid")
+                        .num_columns(7)
+                        .spacing([8.0, 4.0])
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for title in [
+                                "Id",
+                                "Road",
+                                "Start",
+                                "End",
+                                "Removed",
+                                "Last status",
+                                "Map",
+                            ] {
+                                ui.label(RichText::new(title).strong());
+                            }
+                            ui.end_row();
+                            // most recently removed first
+                            for archived in history.roadworks.iter().rev() {
+                                let roadwork = &archived.roadwork;
+                                ui.label(&roadwork.id);
+                                ui.label(roadwork.road.as_deref().unwrap_or_default());
+                                ui.label(format_date(roadwork.start));
+                                ui.label(format_date(roadwork.end));
+                                let removed = format_date(archived.removed.as_millis() as i64);
+                                if archived.is_early() {
+                                    ui.label(RichText::new(removed).color(egui::Color32::ORANGE))
+                                        .on_hover_text("Removed before its end date");
+                                } else if archived.expired {
+                                    ui.label(RichText::new(removed).italics())
+                                        .on_hover_text("Past its end date, still in the service");
+                                } else {
+                                    ui.label(removed);
+                                }
+                                ui.label(roadwork.sync_data.status.to_string());
+                                if ui.button("Show").clicked() {
+                                    action = Some(HistoryAction::Show(LatLng {
+                                        lat: roadwork.latitude,
+                                        lon: roadwork.longitude,
+                                    }));
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+        action
+    }
+}
@@ -0,0 +1,77 @@
+use egui::{Color32, CornerRadius, Rect, Stroke, Ui, vec2};
+
+/// The colors given to the services, they must not be confused with the status colors
+const SOURCE_COLORS: [Color32; 6] = [
+    Color32::from_rgb(128, 0, 128),
+    Color32::from_rgb(0, 128, 128),
+    Color32::from_rgb(255, 0, 255),
+    Color32::from_rgb(139, 69, 19),
+    Color32::BLACK,
+    Color32::from_rgb(70, 130, 180),
+];
+
+/// Returns the color of the nth service shown on the map
+pub(crate) fn source_color(index: usize) -> Color32 {
+    SOURCE_COLORS[index % SOURCE_COLORS.len()]
+}
+
+/// Draw the services shown on the map with their color in the bottom left corner of the map
+pub(crate) struct Legend<'a> {
+    services: &'a [String],
+}
+
+impl<'a> Legend<'a> {
+    pub(crate) fn new(services: &'a [String]) -> Self {
+        Self { services }
+    }
+
+    pub(crate) fn show(self, ui: &Ui, map_rect: Rect) {
+        let painter = ui.painter_at(map_rect);
+        let margin = vec2(6.0, 6.0);
+        let padding = vec2(6.0, 4.0);
+        let text_color = ui.visuals().strong_text_color();
+        let galleys = self
+            .services
+            .iter()
+            .map(|service| {
+                ui.fonts_mut(|f| {
+                    f.layout_no_wrap(
+                        service.clone(),
+                        egui::TextStyle::Body.resolve(ui.style()),
+                        text_color,
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+        let line_height = galleys
+            .iter()
+            .map(|galley| galley.size().y)
+            .fold(0.0, f32::max);
+        let text_width = galleys
+            .iter()
+            .map(|galley| galley.size().x)
+            .fold(0.0, f32::max);
+        let rect_size = vec2(
+            line_height + padding.x + text_width,
+            line_height * galleys.len() as f32,
+        ) + 2.0 * padding;
+        let bottom_left = map_rect.left_bottom() + vec2(margin.x, -margin.y);
+        let rect = Rect::from_min_size(bottom_left - vec2(0.0, rect_size.y), rect_size);
+        painter.rect_filled(rect, CornerRadius::same(4u8), ui.visuals().code_bg_color);
+
+        for (index, galley) in galleys.into_iter().enumerate() {
+            let line_min = rect.min + padding + vec2(0.0, line_height * index as f32);
+            let radius = line_height / 2.0 - 2.0;
+            painter.circle_stroke(
+                line_min + vec2(line_height / 2.0, line_height / 2.0),
+                radius,
+                Stroke::new(3.0, source_color(index)),
+            );
+            painter.galley(
+                line_min + vec2(line_height + padding.x, 0.0),
+                galley,
+                text_color,
+            );
+        }
+    }
+}
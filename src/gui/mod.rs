@@ -1,5 +1,12 @@
 pub(crate) mod about_dialog;
+pub(crate) mod assignee_panel;
+pub(crate) mod conflicts_dialog;
+pub(crate) mod diagnostics_panel;
+pub(crate) mod history_dialog;
+pub(crate) mod legend;
 pub(crate) mod logs_panel;
 pub(crate) mod metada_dialog;
+pub(crate) mod notes_panel;
 pub(crate) mod roadwork_marker;
 pub(crate) mod status_panel;
+pub(crate) mod sync_settings_dialog;
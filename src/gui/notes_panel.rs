@@ -0,0 +1,56 @@
+use crate::model::roadwork::{Roadwork, format_date};
+use egui::{Button, RichText, TextEdit, Ui};
+use roadwork_sync::Note;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The notes of the team on a roadwork, and the note being written
+pub(crate) struct NotesPanel<'a> {
+    roadwork: &'a mut Roadwork,
+    draft: &'a mut String,
+    author: &'a str,
+}
+
+impl<'a> NotesPanel<'a> {
+    pub(crate) fn new(roadwork: &'a mut Roadwork, draft: &'a mut String, author: &'a str) -> Self {
+        Self {
+            roadwork,
+            draft,
+            author,
+        }
+    }
+
+    /// Returns true if the user added a note
+    pub(crate) fn show(self, ui: &mut Ui) -> bool {
+        ui.separator();
+        ui.label(RichText::new("Notes:").strong());
+        for note in self.roadwork.sync_data.notes() {
+            ui.label(
+                RichText::new(format!(
+                    "{} - {}",
+                    note.author,
+                    format_date(note.time as i64)
+                ))
+                .weak(),
+            );
+            ui.label(&note.text);
+        }
+        ui.add(
+            TextEdit::multiline(self.draft)
+                .desired_rows(2)
+                .hint_text("Why it was ignored, the closed segments..."),
+        );
+        let text = self.draft.trim();
+        if !ui
+            .add_enabled(!text.is_empty(), Button::new("Add note"))
+            .clicked()
+        {
+            return false;
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        self.roadwork
+            .sync_data
+            .add_note(Note::new(self.author, now.as_millis() as u64, text));
+        self.draft.clear();
+        true
+    }
+}
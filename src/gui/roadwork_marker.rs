@@ -5,10 +5,32 @@ use egui::{Response, Sense, Ui, Widget};
 use roadwork_sync::Status;
 use walkers::{Position, Projector};
 
+/// Who the roadwork is assigned to, from the point of view of the user
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Assignment {
+    Mine,
+    Other,
+}
+
+impl Assignment {
+    pub(crate) fn of(roadwork: &Roadwork, login: &str) -> Option<Self> {
+        roadwork.sync_data.assignee().map(|assignee| {
+            if assignee == login {
+                Assignment::Mine
+            } else {
+                Assignment::Other
+            }
+        })
+    }
+}
+
 pub struct RoadworkMarker<'a> {
     roadwork: &'a Roadwork,
     projector: &'a Projector,
     clicked: bool,
+    /// The color of the service when several services are shown
+    source_color: Option<Color32>,
+    assignment: Option<Assignment>,
 }
 
 impl<'a> RoadworkMarker<'a> {
@@ -17,8 +39,20 @@ impl<'a> RoadworkMarker<'a> {
             roadwork,
             projector,
             clicked,
+            source_color: None,
+            assignment: None,
         }
     }
+
+    pub(crate) fn with_source_color(mut self, source_color: Option<Color32>) -> Self {
+        self.source_color = source_color;
+        self
+    }
+
+    pub(crate) fn with_assignment(mut self, assignment: Option<Assignment>) -> Self {
+        self.assignment = assignment;
+        self
+    }
 }
 
 impl RoadworkMarker<'_> {
@@ -60,7 +94,43 @@ impl Widget for RoadworkMarker<'_> {
         {
             response.mark_changed();
         }
-        painter.circle(screen_position.to_pos2(), 10., color32, Stroke::default());
+        // a roadwork modified by the opendata service is surrounded to be reviewed
+        let stroke = if self.roadwork.is_modified() {
+            Stroke::new(3.0, Color32::ORANGE)
+        } else {
+            Stroke::default()
+        };
+        painter.circle(screen_position.to_pos2(), 10., color32, stroke);
+        // an assigned roadwork has a dot, white when it is mine and black when it is someone else's
+        match self.assignment {
+            Some(Assignment::Mine) => {
+                painter.circle(
+                    screen_position.to_pos2(),
+                    4.,
+                    Color32::WHITE,
+                    Stroke::new(1.0, Color32::BLACK),
+                );
+            }
+            Some(Assignment::Other) => {
+                painter.circle_filled(screen_position.to_pos2(), 4., Color32::BLACK);
+            }
+            None => {}
+        }
+        if let Some(source_color) = self.source_color {
+            painter.circle_stroke(
+                screen_position.to_pos2(),
+                13.,
+                Stroke::new(2.0, source_color),
+            );
+        }
+        // a status changed by the team is highlighted until the roadwork is reviewed
+        if self.roadwork.changed_by_team {
+            painter.circle_stroke(
+                screen_position.to_pos2(),
+                16.,
+                Stroke::new(2.5, Color32::from_rgb(255, 0, 255)),
+            );
+        }
         response
     }
 }
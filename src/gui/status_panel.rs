@@ -1,6 +1,7 @@
 use crate::model::roadwork::Roadwork;
 use egui::Ui;
 use roadwork_sync::Status;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub(crate) struct StatusPanel<'a> {
     roadwork: &'a mut Roadwork,
@@ -11,38 +12,31 @@ impl<'a> StatusPanel<'a> {
         Self { roadwork }
     }
 
-    pub(crate) fn show(self, ui: &mut Ui) {
+    /// Returns true if the user changed the status
+    pub(crate) fn show(self, ui: &mut Ui) -> bool {
+        let previous_status = self.roadwork.sync_data.status;
+        let mut status = previous_status;
         egui::Grid::new("status_grid")
             .num_columns(2)
             .show(ui, |ui| {
-                ui.radio_value(
-                    &mut self.roadwork.sync_data.status,
-                    Status::New,
-                    Status::New.to_string(),
-                );
-                ui.radio_value(
-                    &mut self.roadwork.sync_data.status,
-                    Status::Later,
-                    Status::Later.to_string(),
-                );
+                ui.radio_value(&mut status, Status::New, Status::New.to_string());
+                ui.radio_value(&mut status, Status::Later, Status::Later.to_string());
                 ui.end_row();
-                ui.radio_value(
-                    &mut self.roadwork.sync_data.status,
-                    Status::Ignored,
-                    Status::Ignored.to_string(),
-                );
-                ui.radio_value(
-                    &mut self.roadwork.sync_data.status,
-                    Status::Finished,
-                    Status::Finished.to_string(),
-                );
+                ui.radio_value(&mut status, Status::Ignored, Status::Ignored.to_string());
+                ui.radio_value(&mut status, Status::Finished, Status::Finished.to_string());
                 ui.end_row();
-                ui.radio_value(
-                    &mut self.roadwork.sync_data.status,
-                    Status::Treated,
-                    Status::Treated.to_string(),
-                );
+                ui.radio_value(&mut status, Status::Treated, Status::Treated.to_string());
                 ui.end_row();
             });
+        // choosing a status means the changes were reviewed
+        if status != previous_status {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            self.roadwork
+                .sync_data
+                .set_status(status, now.as_millis() as u64);
+            self.roadwork.changes.clear();
+            self.roadwork.changed_by_team = false;
+        }
+        status != previous_status
     }
 }
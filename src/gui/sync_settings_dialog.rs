@@ -0,0 +1,141 @@
+use crate::model::roadwork::format_date;
+use crate::service::synchronization_service::SyncState;
+use egui::{Color32, Context, RichText, TextEdit, Ui};
+
+/// The synchronization settings being edited, they are applied when saved
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SyncSettingsForm {
+    pub(crate) url: String,
+    pub(crate) team: String,
+    pub(crate) login: String,
+    pub(crate) password: String,
+    pub(crate) enabled: bool,
+}
+
+/// The result of the connection test
+pub(crate) enum ConnectionTest {
+    Running,
+    Succeeded,
+    Failed(String),
+}
+
+pub(crate) enum SyncSettingsAction {
+    TestConnection,
+    Save,
+}
+
+/// Edit the synchronization settings and show the result of the last synchronization
+pub(crate) struct SyncSettingsDialog<'a> {
+    open: &'a mut bool,
+    form: &'a mut SyncSettingsForm,
+    connection_test: Option<&'a ConnectionTest>,
+    state: SyncState,
+    /// The number of changes waiting to be sent
+    queued: usize,
+}
+
+impl<'a> SyncSettingsDialog<'a> {
+    pub(crate) fn new(
+        open: &'a mut bool,
+        form: &'a mut SyncSettingsForm,
+        connection_test: Option<&'a ConnectionTest>,
+        state: SyncState,
+        queued: usize,
+    ) -> Self {
+        Self {
+            open,
+            form,
+            connection_test,
+            state,
+            queued,
+        }
+    }
+
+    pub(crate) fn show(&mut self, ctx: &Context) -> Option<SyncSettingsAction> {
+        let mut action = None;
+        let form = &mut *self.form;
+        let connection_test = self.connection_test;
+        let state = &self.state;
+        let queued = self.queued;
+        egui::Window::new("Synchronization")
+            .open(self.open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("sync_settings_grid")
+                    .num_columns(2)
+                    .spacing([6.0, 4.0])
+                    .show(ui, |ui| {
+                        ui.label(RichText::new("Server url:").strong());
+                        ui.add(TextEdit::singleline(&mut form.url).hint_text("http://server:8080"));
+                        ui.end_row();
+                        ui.label(RichText::new("Team:").strong());
+                        ui.text_edit_singleline(&mut form.team);
+                        ui.end_row();
+                        ui.label(RichText::new("Login:").strong());
+                        ui.text_edit_singleline(&mut form.login);
+                        ui.end_row();
+                        ui.label(RichText::new("Password:").strong());
+                        ui.add(TextEdit::singleline(&mut form.password).password(true));
+                        ui.end_row();
+                    });
+                ui.checkbox(&mut form.enabled, "Synchronize the statuses with the team");
+                ui.horizontal(|ui| {
+                    let running = matches!(connection_test, Some(ConnectionTest::Running));
+                    if ui
+                        .add_enabled(!running, egui::Button::new("Test connection"))
+                        .clicked()
+                    {
+                        action = Some(SyncSettingsAction::TestConnection);
+                    }
+                    match connection_test {
+                        Some(ConnectionTest::Running) => {
+                            ui.spinner();
+                        }
+                        Some(ConnectionTest::Succeeded) => {
+                            ui.colored_label(Color32::DARK_GREEN, "Connection succeeded");
+                        }
+                        Some(ConnectionTest::Failed(error)) => {
+                            ui.colored_label(Color32::RED, error);
+                        }
+                        None => {}
+                    }
+                });
+                ui.separator();
+                Self::show_state(ui, state, queued);
+                ui.separator();
+                if ui.button("Save").clicked() {
+                    action = Some(SyncSettingsAction::Save);
+                }
+            });
+        action
+    }
+
+    fn show_state(ui: &mut Ui, state: &SyncState, queued: usize) {
+        match state {
+            SyncState::Disabled => {
+                ui.label("No synchronization yet");
+            }
+            SyncState::Synchronized(time) => {
+                ui.colored_label(
+                    Color32::DARK_GREEN,
+                    format!(
+                        "Last synchronization succeeded at {}",
+                        format_date(time.as_millis() as i64)
+                    ),
+                );
+            }
+            SyncState::Failed { time, error } => {
+                ui.colored_label(
+                    Color32::RED,
+                    format!(
+                        "Last synchronization failed at {}: {error}",
+                        format_date(time.as_millis() as i64)
+                    ),
+                );
+            }
+        }
+        if queued > 0 {
+            ui.label(format!("{queued} changes waiting to be sent"));
+        }
+    }
+}
@@ -7,6 +7,7 @@ use serde_json::Value;
 
 pub(crate) trait JsonTools {
     fn get_path(&self, path: &str) -> Result<String, MyError>;
+    fn get_path_as_text(&self, path: &str) -> Result<String, MyError>;
     fn get_path_as_double(&self, path: &str) -> Result<f64, MyError>;
     fn get_path_as_polygons(&self, path: &str) -> Option<Vec<Polygon>>;
 }
@@ -26,6 +27,18 @@ impl JsonTools for &Value {
             .ok_or_else(|| JsonParsingError(format!("Unable to get path {path} from {self}")))
     }
 
+    /// Returns the value as text whatever its type, a string is returned without quotes
+    fn get_path_as_text(&self, path: &str) -> Result<String, MyError> {
+        let result = self.query(path)?;
+        match result.first() {
+            None => Err(JsonParsingError(format!(
+                "Unable to get path {path} from {self}"
+            ))),
+            Some(Value::String(string)) => Ok(string.to_string()),
+            Some(value) => Ok(value.to_string()),
+        }
+    }
+
     fn get_path_as_double(&self, path: &str) -> Result<f64, MyError> {
         let result = self.query(path)?;
         if result.is_empty() {
@@ -102,20 +115,13 @@ mod tests {
     use serde_json::json;
 
     #[test]
-    fn is_multi_polygon() {
-        let json = json!(
-            "[
-            [
-              [
-                2.352152402234131,
-                48.87953656405322
-              ],
-              [
-                2.3524563598371353,
-                48.87932070314852
-              ]
-            ]
-          ]"
-        );
+    fn test_is_multi_polygon() {
+        let multi_polygon = json!([[
+            [2.352152402234131, 48.87953656405322],
+            [2.3524563598371353, 48.87932070314852]
+        ]]);
+        assert!(is_multi_polygon(&vec![&multi_polygon]));
+        let point = json!([2.352152402234131, 48.87953656405322]);
+        assert!(!is_multi_polygon(&vec![&point]));
     }
 }
@@ -1,6 +1,7 @@
 use jsonpath_rust::parser::errors::JsonPathError;
 use thiserror::Error;
 
+mod credentials;
 mod gui;
 mod json_tools;
 mod model;
@@ -8,6 +9,7 @@ mod opendata;
 pub mod roadwork_app;
 mod service;
 pub mod settings;
+mod store;
 
 /// the path where the opendata definitions are stored
 pub(crate) const OPENDATA_FOLDER: &str = "data/opendata";
@@ -38,10 +40,28 @@ pub(crate) enum MyError {
     RoadworkParsingError(String),
     #[error("{0}")]
     ParsingError(String),
+    #[error(
+        "The cache version {0} was written by a newer version of the app, the current version is {1}"
+    )]
+    UnsupportedVersion(u64, u64),
     #[error("{0}")]
     JsonParsingError(String),
     #[error("{0}")]
     JsonPathError(#[from] JsonPathError),
     #[error("{0}")]
     SerdeError(#[from] serde_json::Error),
+    #[error("Io Error {0:?}")]
+    IoError(#[from] std::io::Error),
+    #[error("Zip Error {0:?}")]
+    ZipError(#[from] zip::result::ZipError),
+    #[error("Csv Error {0:?}")]
+    CsvError(#[from] csv::Error),
+    #[error("Sqlite Error {0:?}")]
+    SqliteError(#[from] rusqlite::Error),
+    #[error("{0}")]
+    SyncError(#[from] roadwork_sync::SyncError),
+    #[error("{0}")]
+    CredentialsError(String),
+    #[error("Missing secret {0}, define it in settings or as an environment variable")]
+    MissingSecret(String),
 }
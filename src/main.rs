@@ -1,15 +1,18 @@
 #![windows_subsystem = "windows"]
 
 use egui_extras::install_image_loaders;
-use log::LevelFilter;
+use log::{LevelFilter, warn};
 use roadworkapp_lib::roadwork_app::RoadworkApp;
 use roadworkapp_lib::settings::Settings;
+use std::path::PathBuf;
 
 fn main() -> eframe::Result {
     egui_logger::builder()
         .max_level(LevelFilter::Info)
         .init()
         .unwrap();
+    // the logger is initialized first so the ignored arguments are shown in the logs panel
+    let fixtures = fixtures_folder(std::env::args().skip(1));
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_app_id("Roadwork")
@@ -24,13 +27,32 @@ fn main() -> eframe::Result {
         options,
         Box::new(|ctx| {
             install_image_loaders(&ctx.egui_ctx);
-            let mut app = RoadworkApp::new(ctx.egui_ctx.clone());
+            let mut app = RoadworkApp::new(ctx.egui_ctx.clone(), fixtures);
             app.load_data();
             Ok(Box::new(app))
         }),
     )
 }
 
+/// Returns the fixtures folder if the app is started offline.
+/// `--offline` uses ~/.roadwork/fixtures while `--fixtures <folder>` uses the given folder
+fn fixtures_folder(mut args: impl Iterator<Item = String>) -> Option<PathBuf> {
+    let mut fixtures = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--offline" => {
+                fixtures = Settings::settings_folder().map(|mut folder| {
+                    folder.push("fixtures");
+                    folder
+                })
+            }
+            "--fixtures" => fixtures = args.next().map(PathBuf::from),
+            _ => warn!("Unknown argument {arg}"),
+        }
+    }
+    fixtures
+}
+
 fn icon_data() -> egui::IconData {
     let app_icon_png_bytes = include_bytes!("../media/icon.png");
 
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// A field of a roadwork that was changed by the opendata service
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub(crate) struct FieldChange {
+    pub(crate) field: String,
+    /// The value before the change, empty if there was no value
+    pub(crate) old: String,
+    /// The new value, empty if the value was removed
+    pub(crate) new: String,
+}
+
+impl FieldChange {
+    pub(crate) fn new(field: &str, old: String, new: String) -> Self {
+        Self {
+            field: field.to_string(),
+            old,
+            new,
+        }
+    }
+}
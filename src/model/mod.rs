@@ -1,4 +1,8 @@
 pub(crate) mod date_range;
+pub(crate) mod field_change;
+pub(crate) mod parse_diagnostic;
 pub(crate) mod roadwork;
 pub(crate) mod roadwork_data;
+pub(crate) mod roadwork_history;
+pub(crate) mod validators;
 pub(crate) mod wkt;
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+/// A problem found while parsing a record of an opendata service.
+/// It is kept with the roadwork data so the rejected records can be reviewed
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct ParseDiagnostic {
+    /// The index of the record in the roadwork array
+    pub(crate) index: usize,
+    #[serde(rename = "recordId")]
+    pub(crate) record_id: Option<String>,
+    /// The roadwork field that could not be parsed
+    pub(crate) field: String,
+    /// The JSONPath of the field in the descriptor
+    pub(crate) path: Option<String>,
+    #[serde(rename = "rawValue")]
+    pub(crate) raw_value: Option<String>,
+    pub(crate) error: String,
+    /// true if the whole record was dropped, false if only the field is missing
+    pub(crate) rejected: bool,
+    /// The raw json of the record
+    pub(crate) record: String,
+}
@@ -1,11 +1,13 @@
+use crate::model::field_change::FieldChange;
 use crate::model::wkt::polygon::Polygon;
-use roadwork_sync::SyncData;
+use chrono::DateTime;
+use roadwork_sync::{Status, SyncData};
 use serde::{Deserialize, Serialize};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Roadwork structure
 /// it is serialized as a cache on disk
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub(crate) struct Roadwork {
     pub(crate) id: String,
     pub(crate) latitude: f64,
@@ -22,6 +24,12 @@ pub(crate) struct Roadwork {
     #[serde(rename = "syncData")]
     pub(crate) sync_data: SyncData,
     pub(crate) url: String,
+    /// The changes made by the opendata service since the roadwork was last reviewed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) changes: Vec<FieldChange>,
+    /// True if another member of the team changed the status or the assignee, or added a note, since the roadwork was last selected
+    #[serde(skip)]
+    pub(crate) changed_by_team: bool,
 }
 
 impl Roadwork {
@@ -29,4 +37,187 @@ impl Roadwork {
         Duration::from_millis(self.end as u64)
             < SystemTime::now().duration_since(UNIX_EPOCH).unwrap()
     }
+
+    pub(crate) fn is_modified(&self) -> bool {
+        !self.changes.is_empty()
+    }
+
+    /// Take the state of the previous version of this roadwork.
+    /// The status is kept with its pending synchronization, and if the opendata service changed some fields they are recorded,
+    /// a Treated or Ignored roadwork is then reopened as it has to be reviewed again
+    pub(crate) fn update_from(&mut self, previous: &Roadwork) {
+        self.sync_data = previous.sync_data.clone();
+        let mut changes = previous.changes.clone();
+        for change in previous.diff(self) {
+            match changes.iter_mut().find(|c| c.field == change.field) {
+                Some(existing) => existing.new = change.new,
+                None => changes.push(change),
+            }
+        }
+        // a field that went back to its reviewed value is not a change anymore
+        changes.retain(|change| change.old != change.new);
+        let has_new_changes = changes
+            .iter()
+            .any(|change| !previous.changes.contains(change));
+        if has_new_changes && matches!(self.sync_data.status, Status::Treated | Status::Ignored) {
+            // the reopening is a local change, it is sent to the team
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            self.sync_data
+                .set_status(Status::New, now.as_millis() as u64);
+        }
+        self.changes = changes;
+    }
+
+    /// Returns the fields that are different in the other roadwork
+    pub(crate) fn diff(&self, other: &Roadwork) -> Vec<FieldChange> {
+        let mut changes = Vec::new();
+        let mut compare = |field: &str, old: String, new: String| {
+            if old != new {
+                changes.push(FieldChange::new(field, old, new));
+            }
+        };
+        compare("Start", format_date(self.start), format_date(other.start));
+        compare("End", format_date(self.end), format_date(other.end));
+        compare(
+            "Latitude",
+            self.latitude.to_string(),
+            other.latitude.to_string(),
+        );
+        compare(
+            "Longitude",
+            self.longitude.to_string(),
+            other.longitude.to_string(),
+        );
+        compare(
+            "Road",
+            self.road.clone().unwrap_or_default(),
+            other.road.clone().unwrap_or_default(),
+        );
+        compare(
+            "Location details",
+            self.location_details.clone().unwrap_or_default(),
+            other.location_details.clone().unwrap_or_default(),
+        );
+        compare(
+            "Impact",
+            self.impact_circulation_detail.clone().unwrap_or_default(),
+            other.impact_circulation_detail.clone().unwrap_or_default(),
+        );
+        compare(
+            "Description",
+            self.description.clone().unwrap_or_default(),
+            other.description.clone().unwrap_or_default(),
+        );
+        compare("Url", self.url.clone(), other.url.clone());
+        changes
+    }
+}
+
+/// Format a timestamp in milliseconds, 0 means there is no date
+pub(crate) fn format_date(timestamp: i64) -> String {
+    if timestamp == 0 {
+        return String::new();
+    }
+    DateTime::from_timestamp_millis(timestamp)
+        .map(|date| date.format("%d/%m/%Y %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+/// Format a duration as a short text like "3 h" or "2 d"
+pub(crate) fn format_age(age: Duration) -> String {
+    let minutes = age.as_secs() / 60;
+    match minutes {
+        0..60 => format!("{minutes} min"),
+        60..1440 => format!("{} h", minutes / 60),
+        _ => format!("{} d", minutes / 1440),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roadwork() -> Roadwork {
+        Roadwork {
+            id: "1".to_string(),
+            latitude: 48.85,
+            longitude: 2.34,
+            start: 1739145600000,
+            end: 1741564800000,
+            road: Some("Rue de Rivoli".to_string()),
+            ..Roadwork::default()
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let previous = roadwork();
+        let mut current = roadwork();
+        assert!(previous.diff(&current).is_empty());
+        current.end = 1744243200000;
+        current.road = None;
+        assert_eq!(
+            previous.diff(&current),
+            vec![
+                FieldChange::new(
+                    "End",
+                    "10/03/2025 00:00".to_string(),
+                    "10/04/2025 00:00".to_string()
+                ),
+                FieldChange::new("Road", "Rue de Rivoli".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_from_keeps_status() {
+        let mut previous = roadwork();
+        previous.sync_data.status = Status::Treated;
+        let mut current = roadwork();
+        current.update_from(&previous);
+        assert_eq!(current.sync_data.status, Status::Treated);
+        assert!(!current.sync_data.is_dirty());
+        assert!(!current.is_modified());
+    }
+
+    #[test]
+    fn test_update_from_reopens_treated() {
+        let mut previous = roadwork();
+        previous.sync_data.status = Status::Treated;
+        let mut current = roadwork();
+        current.end = 1744243200000;
+        current.update_from(&previous);
+        assert_eq!(current.sync_data.status, Status::New);
+        assert!(current.sync_data.is_dirty());
+        assert_eq!(
+            current.changes,
+            vec![FieldChange::new(
+                "End",
+                "10/03/2025 00:00".to_string(),
+                "10/04/2025 00:00".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_update_from_keeps_unreviewed_changes() {
+        let previous = roadwork();
+        let mut current = roadwork();
+        current.end = 1744243200000;
+        current.update_from(&previous);
+        assert_eq!(current.changes.len(), 1);
+
+        // the end date goes back to the reviewed value
+        let previous = current;
+        let mut current = roadwork();
+        current.update_from(&previous);
+        assert!(!current.is_modified());
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(Duration::from_secs(59)), "0 min");
+        assert_eq!(format_age(Duration::from_secs(3 * 3600 + 60)), "3 h");
+        assert_eq!(format_age(Duration::from_secs(2 * 86400)), "2 d");
+    }
 }
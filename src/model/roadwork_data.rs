@@ -1,4 +1,8 @@
+use crate::model::parse_diagnostic::ParseDiagnostic;
 use crate::model::roadwork::Roadwork;
+use crate::model::validators::Validators;
+use log::info;
+use roadwork_sync::SyncRequest;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
@@ -14,6 +18,17 @@ pub(crate) struct RoadworkData {
      * The date of the last update
      */
     pub(crate) created: Duration,
+    /**
+     * The problems found while parsing the opendata service
+     */
+    #[serde(default)]
+    pub(crate) diagnostics: Vec<ParseDiagnostic>,
+    /// Used to ask the service if the data changed since it was fetched
+    #[serde(default, skip_serializing_if = "Validators::is_empty")]
+    pub(crate) validators: Validators,
+    /// True when the last refresh failed and this is the previously fetched data
+    #[serde(skip)]
+    pub(crate) stale: bool,
 }
 
 impl<'a> IntoIterator for &'a mut RoadworkData {
@@ -35,14 +50,110 @@ impl RoadworkData {
             source: source.to_string(),
             roadworks: roadworks_map,
             created: SystemTime::now().duration_since(UNIX_EPOCH).unwrap(),
+            diagnostics: Vec::new(),
+            validators: Validators::default(),
+            stale: false,
         }
     }
 
+    /// Returns the time elapsed since the data was fetched
+    pub(crate) fn age(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .saturating_sub(self.created)
+    }
+
     pub(crate) fn iter(&self) -> impl Iterator<Item = &Roadwork> {
         self.roadworks.values()
     }
 
+    /// Take the state of the roadworks of the previous data, and detect the changes.
+    /// Returns the roadworks that are not in the service anymore
+    pub(crate) fn merge(&mut self, previous: RoadworkData) -> Vec<Roadwork> {
+        let mut removed = Vec::new();
+        for previous_roadwork in previous.roadworks.into_values() {
+            match self.roadworks.get_mut(&previous_roadwork.id) {
+                Some(roadwork) => {
+                    roadwork.update_from(&previous_roadwork);
+                    info!(
+                        "Roadwork {} -> status {}",
+                        roadwork.id, roadwork.sync_data.status
+                    );
+                }
+                None => removed.push(previous_roadwork),
+            }
+        }
+        let modified_count = self.iter().filter(|r| r.is_modified()).count();
+        if modified_count > 0 {
+            info!("{modified_count} roadworks were modified");
+        }
+        if !removed.is_empty() {
+            info!("{} roadworks were removed", removed.len());
+        }
+        removed
+    }
+
+    /// Returns the number of records that were dropped while parsing
+    pub(crate) fn rejected_count(&self) -> usize {
+        self.diagnostics.iter().filter(|d| d.rejected).count()
+    }
+
+    /// Returns the request sending the status changed locally, and receiving the changes of the team
+    /// since the most recent one already received
+    pub(crate) fn sync_request(&self) -> SyncRequest {
+        let mut request = SyncRequest::new(
+            &self.source,
+            self.iter()
+                .map(|roadwork| roadwork.sync_data.server_update_time())
+                .max()
+                .unwrap_or_default(),
+        );
+        request.sync_data = self
+            .iter()
+            .filter(|roadwork| roadwork.sync_data.is_dirty())
+            .map(|roadwork| (roadwork.id.clone(), roadwork.sync_data.clone()))
+            .collect();
+        request
+    }
+
     pub(crate) fn get_mut_roadwork(&mut self, id: &str) -> Option<&mut Roadwork> {
         self.roadworks.get_mut(id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use roadwork_sync::Status;
+
+    fn roadwork(id: &str, status: Status) -> Roadwork {
+        let mut roadwork = Roadwork {
+            id: id.to_string(),
+            ..Roadwork::default()
+        };
+        roadwork.sync_data.status = status;
+        roadwork
+    }
+
+    #[test]
+    fn test_merge() {
+        let previous = RoadworkData::new(
+            "France-Paris",
+            vec![
+                roadwork("1", Status::Treated),
+                roadwork("2", Status::Ignored),
+            ],
+        );
+        let mut new_data = RoadworkData::new(
+            "France-Paris",
+            vec![roadwork("1", Status::New), roadwork("3", Status::New)],
+        );
+        let removed = new_data.merge(previous);
+        assert_eq!(new_data.roadworks["1"].sync_data.status, Status::Treated);
+        assert_eq!(new_data.roadworks["3"].sync_data.status, Status::New);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].id, "2");
+        assert_eq!(removed[0].sync_data.status, Status::Ignored);
+    }
+}
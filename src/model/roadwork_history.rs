@@ -0,0 +1,180 @@
+use crate::MyError;
+use crate::model::roadwork::{Roadwork, format_date};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The roadworks that disappeared from an opendata service
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct RoadworkHistory {
+    /**
+     * The name of the opendata source
+     */
+    pub(crate) source: String,
+    pub(crate) roadworks: Vec<ArchivedRoadwork>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct ArchivedRoadwork {
+    /// The last known version of the roadwork, with its last status
+    pub(crate) roadwork: Roadwork,
+    /**
+     * The date the roadwork disappeared from the service, or the date it was found past its end date
+     */
+    pub(crate) removed: Duration,
+    /// True if the roadwork is past its end date but still in the service
+    #[serde(default)]
+    pub(crate) expired: bool,
+}
+
+/// A line of the csv export
+#[derive(Serialize)]
+struct ExportRecord<'a> {
+    id: &'a str,
+    road: &'a str,
+    start: String,
+    end: String,
+    removed: String,
+    status: &'static str,
+    /// true if the roadwork disappeared before its end date
+    early: bool,
+    /// true if the roadwork is past its end date but still in the service
+    expired: bool,
+    description: &'a str,
+}
+
+impl ArchivedRoadwork {
+    /// Returns true if the roadwork disappeared before its end date,
+    /// it was either finished early or removed from the service by mistake
+    pub(crate) fn is_early(&self) -> bool {
+        self.roadwork.end > 0 && (self.roadwork.end as u128) > self.removed.as_millis()
+    }
+}
+
+impl RoadworkHistory {
+    pub(crate) fn new(source: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            roadworks: Vec::new(),
+        }
+    }
+
+    /// Archive the roadworks removed from the service,
+    /// they replace their entry if they were archived when they expired
+    pub(crate) fn archive(&mut self, removed: Vec<Roadwork>) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        for roadwork in removed {
+            self.restore(&roadwork.id);
+            self.roadworks.push(ArchivedRoadwork {
+                roadwork,
+                removed: now,
+                expired: false,
+            });
+        }
+    }
+
+    /// Archive the roadworks of the service that are past their end date, or update their entry
+    /// with their last status. The entry of a roadwork whose end date was pushed back is removed
+    pub(crate) fn archive_expired<'a>(&mut self, roadworks: impl Iterator<Item = &'a Roadwork>) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        for roadwork in roadworks {
+            let index = self
+                .roadworks
+                .iter()
+                .position(|archived| archived.expired && archived.roadwork.id == roadwork.id);
+            match (index, roadwork.is_expired()) {
+                (Some(index), true) => self.roadworks[index].roadwork = roadwork.clone(),
+                (Some(index), false) => {
+                    self.roadworks.remove(index);
+                }
+                (None, true) => self.roadworks.push(ArchivedRoadwork {
+                    roadwork: roadwork.clone(),
+                    removed: now,
+                    expired: true,
+                }),
+                (None, false) => {}
+            }
+        }
+    }
+
+    /// Remove a roadwork from the history if it is back in the service
+    pub(crate) fn restore(&mut self, id: &str) -> Option<Roadwork> {
+        let index = self.roadworks.iter().position(|r| r.roadwork.id == id)?;
+        Some(self.roadworks.remove(index).roadwork)
+    }
+
+    pub(crate) fn export_csv(&self, path: &Path) -> Result<(), MyError> {
+        let mut writer = csv::Writer::from_path(path)?;
+        for archived in &self.roadworks {
+            let roadwork = &archived.roadwork;
+            writer.serialize(ExportRecord {
+                id: &roadwork.id,
+                road: roadwork.road.as_deref().unwrap_or_default(),
+                start: format_date(roadwork.start),
+                end: format_date(roadwork.end),
+                removed: format_date(archived.removed.as_millis() as i64),
+                status: roadwork.sync_data.status.into(),
+                early: archived.is_early(),
+                expired: archived.expired,
+                description: roadwork.description.as_deref().unwrap_or_default(),
+            })?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roadwork(id: &str) -> Roadwork {
+        Roadwork {
+            id: id.to_string(),
+            end: 4102444800000,
+            ..Roadwork::default()
+        }
+    }
+
+    #[test]
+    fn test_archive_and_restore() {
+        let mut history = RoadworkHistory::new("France-Paris");
+        history.archive(vec![roadwork("1"), roadwork("2")]);
+        assert_eq!(history.roadworks.len(), 2);
+        assert!(history.roadworks[0].is_early());
+        assert_eq!(history.restore("1").map(|r| r.id), Some("1".to_string()));
+        assert!(history.restore("1").is_none());
+        assert_eq!(history.roadworks.len(), 1);
+    }
+
+    #[test]
+    fn test_archive_expired() {
+        let mut history = RoadworkHistory::new("France-Paris");
+        let expired = Roadwork {
+            end: 1000,
+            ..roadwork("1")
+        };
+        history.archive_expired([&expired, &roadwork("2")].into_iter());
+        history.archive_expired([&expired].into_iter());
+        assert_eq!(history.roadworks.len(), 1);
+        assert!(history.roadworks[0].expired);
+        assert!(!history.roadworks[0].is_early());
+
+        // it disappears from the service, its entry is replaced
+        history.archive(vec![expired]);
+        assert_eq!(history.roadworks.len(), 1);
+        assert!(!history.roadworks[0].expired);
+
+        // its end date was pushed back
+        let extended = roadwork("3");
+        history.archive_expired(
+            [&Roadwork {
+                end: 1000,
+                ..extended.clone()
+            }]
+            .into_iter(),
+        );
+        history.archive_expired([&extended].into_iter());
+        assert_eq!(history.roadworks.len(), 1);
+    }
+}
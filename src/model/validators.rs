@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+/// The HTTP validators of the last response of an opendata service,
+/// they are sent back so the service can answer that the data did not change
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub(crate) struct Validators {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) etag: Option<String>,
+    #[serde(
+        rename = "lastModified",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(crate) last_modified: Option<String>,
+}
+
+impl Validators {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    /// Returns the headers of a conditional request
+    pub(crate) fn headers(&self) -> Vec<(String, String)> {
+        let mut headers = Vec::new();
+        if let Some(etag) = &self.etag {
+            headers.push(("If-None-Match".to_string(), etag.clone()));
+        }
+        if let Some(last_modified) = &self.last_modified {
+            headers.push(("If-Modified-Since".to_string(), last_modified.clone()));
+        }
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headers() {
+        assert!(Validators::default().headers().is_empty());
+        let validators = Validators {
+            etag: Some("\"33a64df5\"".to_string()),
+            last_modified: Some("Wed, 21 Oct 2025 07:28:00 GMT".to_string()),
+        };
+        assert_eq!(
+            validators.headers(),
+            vec![
+                ("If-None-Match".to_string(), "\"33a64df5\"".to_string()),
+                (
+                    "If-Modified-Since".to_string(),
+                    "Wed, 21 Oct 2025 07:28:00 GMT".to_string()
+                )
+            ]
+        );
+    }
+}
@@ -18,7 +18,7 @@ struct IndexEntry {
     path: String,
 }
 
-pub(crate) fn ensure_opendata_available() {
+pub(crate) fn ensure_opendata_available(http: &HttpService) {
     let opendata_folder = crate::opendata_folder_path();
     if opendata_folder.exists() {
         info!(
@@ -32,7 +32,7 @@ pub(crate) fn ensure_opendata_available() {
         "Bootstrap: {} not found, downloading descriptors",
         opendata_folder.display()
     );
-    if let Err(e) = bootstrap_download(&opendata_folder) {
+    if let Err(e) = bootstrap_download(&opendata_folder, http) {
         error!("Bootstrap failed: {e}");
         return;
     }
@@ -45,9 +45,7 @@ pub(crate) fn ensure_opendata_available() {
     }
 }
 
-fn bootstrap_download(base_folder: &Path) -> Result<(), String> {
-    let http = HttpService;
-
+fn bootstrap_download(base_folder: &Path, http: &HttpService) -> Result<(), String> {
     // Download index.json from raw GitHub URL
     let index_url = format!("{GITHUB_RAW_PREFIX}/index.json");
     let index_str = http
@@ -0,0 +1,97 @@
+use crate::model::roadwork_data::RoadworkData;
+use crate::opendata::open_data_service_manager::OpenDataServiceManager;
+use egui::Context;
+use log::info;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+
+/// The progress of a loading, shared between the worker and the UI
+#[derive(Debug, Default)]
+pub(crate) struct Progress {
+    step: Mutex<String>,
+    cancelled: AtomicBool,
+}
+
+impl Progress {
+    pub(crate) fn set_step(&self, step: impl Into<String>) {
+        *self.step.lock().unwrap() = step.into();
+    }
+
+    pub(crate) fn step(&self) -> String {
+        self.step.lock().unwrap().clone()
+    }
+
+    pub(crate) fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Load the data of a service on a worker thread, so the UI stays interactive
+/// with the previous data until the new one arrives
+pub(crate) struct DataLoader {
+    pub(crate) source: String,
+    /// True if the data is fetched again even if the cache is recent
+    pub(crate) force: bool,
+    progress: Arc<Progress>,
+    receiver: Receiver<Option<RoadworkData>>,
+}
+
+/// The end of a loading
+pub(crate) enum LoadResult {
+    Loaded(Option<RoadworkData>),
+    /// The worker stopped without a result, it panicked
+    Failed,
+}
+
+impl DataLoader {
+    /// Start loading the data, the UI is repainted when it is done
+    pub(crate) fn start(
+        manager: Arc<OpenDataServiceManager>,
+        source: String,
+        force: bool,
+        egui_ctx: Context,
+    ) -> Self {
+        info!("Start loading {source}");
+        let progress = Arc::new(Progress::default());
+        let (sender, receiver) = mpsc::channel();
+        let worker_progress = Arc::clone(&progress);
+        let worker_source = source.clone();
+        thread::spawn(move || {
+            let roadwork_data = manager.load_data(&worker_source, force, &worker_progress);
+            // the loader is dropped when it is cancelled
+            sender.send(roadwork_data).ok();
+            egui_ctx.request_repaint();
+        });
+        Self {
+            source,
+            force,
+            progress,
+            receiver,
+        }
+    }
+
+    pub(crate) fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    /// Stop waiting for the data, the worker does not save anything it fetches afterward
+    pub(crate) fn cancel(&self) {
+        info!("Cancel loading {}", self.source);
+        self.progress.cancel();
+    }
+
+    /// Returns the result once the worker is done
+    pub(crate) fn poll(&self) -> Option<LoadResult> {
+        match self.receiver.try_recv() {
+            Ok(roadwork_data) => Some(LoadResult::Loaded(roadwork_data)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(LoadResult::Failed),
+        }
+    }
+}
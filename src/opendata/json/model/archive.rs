@@ -0,0 +1,131 @@
+use crate::MyError;
+use flate2::read::GzDecoder;
+use log::info;
+use serde::Deserialize;
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+
+/// Describes a compressed payload
+#[derive(Debug, Deserialize)]
+pub(crate) struct Archive {
+    #[serde(rename = "type")]
+    pub(crate) archive_type: ArchiveType,
+    /// The name of the file to extract from a zip archive. If missing the first file is used
+    pub(crate) file: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ArchiveType {
+    Zip,
+    Gzip,
+}
+
+impl Archive {
+    /// Returns a reader on the decompressed document
+    pub(crate) fn extract(&self, reader: Box<dyn Read>) -> Result<Box<dyn Read>, MyError> {
+        match self.archive_type {
+            ArchiveType::Gzip => Ok(Box::new(GzDecoder::new(reader))),
+            ArchiveType::Zip => {
+                // zip needs to seek in the archive so it has to be loaded in memory
+                let mut bytes = Vec::new();
+                let mut reader = reader;
+                reader.read_to_end(&mut bytes)?;
+                let mut zip_archive = ZipArchive::new(Cursor::new(bytes))?;
+                let index = match &self.file {
+                    Some(file) => zip_archive.index_for_name(file).ok_or_else(|| {
+                        MyError::ParsingError(format!("File {file} not found in zip archive"))
+                    })?,
+                    None => (0..zip_archive.len())
+                        .find(|&i| zip_archive.by_index(i).is_ok_and(|file| file.is_file()))
+                        .ok_or_else(|| MyError::ParsingError("Zip archive is empty".to_string()))?,
+                };
+                let mut file = zip_archive.by_index(index)?;
+                info!("Extracting {} from zip archive", file.name());
+                let mut content = Vec::with_capacity(file.size() as usize);
+                file.read_to_end(&mut content)?;
+                Ok(Box::new(Cursor::new(content)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    const CONTENT: &str = r#"{"records": []}"#;
+
+    fn read_all(mut reader: Box<dyn Read>) -> String {
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        content
+    }
+
+    fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_deserialize() -> Result<(), MyError> {
+        let archive: Archive =
+            serde_json::from_str(r#"{"type": "zip", "file": "chantiers.geojson"}"#)?;
+        assert_eq!(archive.archive_type, ArchiveType::Zip);
+        assert_eq!(archive.file, Some("chantiers.geojson".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_gzip() -> Result<(), MyError> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(CONTENT.as_bytes())?;
+        let archive = Archive {
+            archive_type: ArchiveType::Gzip,
+            file: None,
+        };
+        let reader = archive.extract(Box::new(Cursor::new(encoder.finish()?)))?;
+        assert_eq!(read_all(reader), CONTENT);
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_zip() -> Result<(), MyError> {
+        let bytes = zip(&[("readme.txt", "readme"), ("data.json", CONTENT)]);
+        let archive = Archive {
+            archive_type: ArchiveType::Zip,
+            file: Some("data.json".to_string()),
+        };
+        let reader = archive.extract(Box::new(Cursor::new(bytes.clone())))?;
+        assert_eq!(read_all(reader), CONTENT);
+
+        let archive = Archive {
+            archive_type: ArchiveType::Zip,
+            file: None,
+        };
+        let reader = archive.extract(Box::new(Cursor::new(bytes)))?;
+        assert_eq!(read_all(reader), "readme");
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_zip_missing_file() {
+        let archive = Archive {
+            archive_type: ArchiveType::Zip,
+            file: Some("missing.json".to_string()),
+        };
+        let result = archive.extract(Box::new(Cursor::new(zip(&[("data.json", CONTENT)]))));
+        assert!(result.is_err());
+    }
+}
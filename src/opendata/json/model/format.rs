@@ -0,0 +1,60 @@
+use crate::MyError;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::io::{BufReader, Read};
+
+/// The format of the document returned by the service
+#[derive(Debug, Default, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Format {
+    #[default]
+    Json,
+    /// A csv file with a header line, every row is converted to a json object
+    /// so the roadworkArray path is `$[*]`
+    Csv,
+}
+
+impl Format {
+    /// Read the document as json, the reader is consumed as a stream
+    pub(crate) fn read(&self, reader: Box<dyn Read>) -> Result<Value, MyError> {
+        match self {
+            Format::Json => Ok(serde_json::from_reader(BufReader::new(reader))?),
+            Format::Csv => {
+                let mut csv_reader = csv::Reader::from_reader(reader);
+                let headers = csv_reader.headers()?.clone();
+                let mut rows = Vec::new();
+                for record in csv_reader.records() {
+                    let record = record?;
+                    let row = headers
+                        .iter()
+                        .zip(record.iter())
+                        .map(|(header, value)| (header.to_string(), Value::from(value)))
+                        .collect::<Map<String, Value>>();
+                    rows.push(Value::Object(row));
+                }
+                Ok(Value::Array(rows))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_csv() -> Result<(), MyError> {
+        let csv = "id,lat,lon\n1,48.85,2.34\n2,48.86,2.35\n";
+        let value = Format::Csv.read(Box::new(Cursor::new(csv)))?;
+        assert_eq!(
+            value,
+            json!([
+                {"id": "1", "lat": "48.85", "lon": "2.34"},
+                {"id": "2", "lat": "48.86", "lon": "2.35"}
+            ])
+        );
+        Ok(())
+    }
+}
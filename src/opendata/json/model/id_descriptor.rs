@@ -0,0 +1,154 @@
+use crate::MyError;
+use crate::json_tools::JsonTools;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Describes how the id of a roadwork is built.
+/// It is either the path of a single field, or a combination of several fields for the services
+/// that have no stable id. With `hash` the combined values are replaced by a stable hash
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub(crate) enum IdDescriptor {
+    Path(String),
+    Composite {
+        paths: Vec<String>,
+        #[serde(default = "default_separator")]
+        separator: String,
+        #[serde(default)]
+        hash: bool,
+    },
+}
+
+fn default_separator() -> String {
+    "|".to_string()
+}
+
+impl IdDescriptor {
+    /// Returns the path(s) used to build the id
+    pub(crate) fn path(&self) -> String {
+        match self {
+            IdDescriptor::Path(path) => path.clone(),
+            IdDescriptor::Composite { paths, .. } => paths.join(", "),
+        }
+    }
+
+    pub(crate) fn build(&self, node: &Value) -> Result<String, MyError> {
+        match self {
+            IdDescriptor::Path(path) => node.get_path_as_text(path),
+            IdDescriptor::Composite {
+                paths,
+                separator,
+                hash,
+            } => {
+                let values = paths
+                    .iter()
+                    .map(|path| node.get_path_as_text(path).ok())
+                    .collect::<Vec<_>>();
+                if values.iter().all(Option::is_none) {
+                    return Err(MyError::RoadworkParsingError(format!(
+                        "Unable to build id from {paths:?}"
+                    )));
+                }
+                let id = values
+                    .into_iter()
+                    .map(Option::unwrap_or_default)
+                    .collect::<Vec<_>>()
+                    .join(separator);
+                if *hash {
+                    Ok(format!("{:016x}", fnv1a(id.as_bytes())))
+                } else {
+                    Ok(id)
+                }
+            }
+        }
+    }
+}
+
+/// FNV-1a hash, unlike the std hasher it is guaranteed to be stable between releases
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn roadwork() -> Value {
+        json!({
+            "id": "1",
+            "road": "Rue de Rivoli",
+            "start": "2025-02-10",
+            "geometry": {"coordinates": [2.34, 48.85]}
+        })
+    }
+
+    #[test]
+    fn test_deserialize() -> Result<(), MyError> {
+        let id: IdDescriptor = serde_json::from_str(r#""$.id""#)?;
+        assert_eq!(id, IdDescriptor::Path("$.id".to_string()));
+        let id: IdDescriptor = serde_json::from_str(r#"{"paths": ["$.road", "$.start"]}"#)?;
+        assert_eq!(
+            id,
+            IdDescriptor::Composite {
+                paths: vec!["$.road".to_string(), "$.start".to_string()],
+                separator: "|".to_string(),
+                hash: false
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_composite() -> Result<(), MyError> {
+        let id = IdDescriptor::Composite {
+            paths: vec![
+                "$.road".to_string(),
+                "$.start".to_string(),
+                "$.missing".to_string(),
+            ],
+            separator: "|".to_string(),
+            hash: false,
+        };
+        assert_eq!(id.build(&roadwork())?, "Rue de Rivoli|2025-02-10|");
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_hash() -> Result<(), MyError> {
+        let id = IdDescriptor::Composite {
+            paths: vec![
+                "$.road".to_string(),
+                "$.start".to_string(),
+                "$.geometry".to_string(),
+            ],
+            separator: "|".to_string(),
+            hash: true,
+        };
+        let hash = id.build(&roadwork())?;
+        assert_eq!(hash.len(), 16);
+        assert_eq!(hash, id.build(&roadwork())?);
+        assert_ne!(hash, id.build(&json!({"road": "Rue de Rivoli"}))?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_missing() {
+        let id = IdDescriptor::Composite {
+            paths: vec!["$.missing".to_string()],
+            separator: "|".to_string(),
+            hash: true,
+        };
+        assert!(id.build(&roadwork()).is_err());
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+    }
+}
@@ -23,6 +23,9 @@ pub(crate) struct Metadata {
     tile_server: Option<String>,
     #[serde(rename = "editorPattern")]
     pub(crate) editor_pattern: Option<String>,
+    /// The number of seconds the data of the service is kept before being fetched again
+    #[serde(rename = "refreshInterval")]
+    pub(crate) refresh_interval: Option<u64>,
 }
 
 impl Metadata {
@@ -60,18 +63,6 @@ impl Metadata {
     }
 }
 
-impl Metadata {
-    pub(crate) fn new() -> Self {
-        Self {
-            tile_server: Some("WazeINTL".to_string()),
-            editor_pattern: Some(
-                "https://waze.com/fr/editor?env=row&lat=${lat}&lon=${lon}&zoomLevel=19".to_string(),
-            ),
-            ..Default::default()
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
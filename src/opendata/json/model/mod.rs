@@ -1,6 +1,10 @@
+pub(crate) mod archive;
 pub(crate) mod date_parser;
 pub(crate) mod date_result;
+pub(crate) mod format;
+pub(crate) mod id_descriptor;
 pub(crate) mod lat_lng;
 pub(crate) mod metadata;
 pub(crate) mod parser;
+pub(crate) mod request;
 pub(crate) mod service_descriptor;
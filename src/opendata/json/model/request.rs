@@ -0,0 +1,175 @@
+use crate::MyError;
+use crate::service::http_service::basic_auth;
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+/// Matches `${NAME}` placeholders in request values
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\$\{([A-Za-z0-9_.-]+)}").unwrap());
+
+/// Describes how the opendata url must be requested.
+/// Values can contain `${NAME}` placeholders, they are resolved from the user settings
+/// (`sourceSecrets`) first, then from the environment variables, so the secrets never
+/// have to be written in the shared descriptor file.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct Request {
+    #[serde(default)]
+    pub(crate) method: Method,
+    #[serde(default)]
+    pub(crate) headers: HashMap<String, String>,
+    pub(crate) body: Option<String>,
+    pub(crate) auth: Option<Auth>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub(crate) enum Method {
+    #[default]
+    Get,
+    Post,
+}
+
+impl From<Method> for reqwest::Method {
+    fn from(value: Method) -> Self {
+        match value {
+            Method::Get => reqwest::Method::GET,
+            Method::Post => reqwest::Method::POST,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub(crate) enum Auth {
+    Basic { login: String, password: String },
+    Bearer { token: String },
+}
+
+impl Request {
+    /// Returns the headers with their placeholders resolved, including the Authorization header
+    pub(crate) fn resolve_headers(
+        &self,
+        secrets: &HashMap<String, String>,
+    ) -> Result<Vec<(String, String)>, MyError> {
+        let mut headers = Vec::with_capacity(self.headers.len() + 1);
+        for (name, value) in &self.headers {
+            headers.push((name.clone(), resolve(value, secrets)?));
+        }
+        if let Some(auth) = &self.auth {
+            headers.push(("Authorization".to_string(), auth.header_value(secrets)?));
+        }
+        Ok(headers)
+    }
+
+    pub(crate) fn resolve_body(
+        &self,
+        secrets: &HashMap<String, String>,
+    ) -> Result<Option<String>, MyError> {
+        self.body
+            .as_ref()
+            .map(|body| resolve(body, secrets))
+            .transpose()
+    }
+}
+
+impl Auth {
+    fn header_value(&self, secrets: &HashMap<String, String>) -> Result<String, MyError> {
+        match self {
+            Auth::Basic { login, password } => Ok(basic_auth(
+                &resolve(login, secrets)?,
+                &resolve(password, secrets)?,
+            )),
+            Auth::Bearer { token } => Ok(format!("Bearer {}", resolve(token, secrets)?)),
+        }
+    }
+}
+
+/// Replace the `${NAME}` placeholders of the value using the secrets or the environment variables
+pub(crate) fn resolve(value: &str, secrets: &HashMap<String, String>) -> Result<String, MyError> {
+    let mut missing = None;
+    let resolved = PLACEHOLDER.replace_all(value, |captures: &Captures| {
+        let name = &captures[1];
+        secrets
+            .get(name)
+            .cloned()
+            .or_else(|| std::env::var(name).ok())
+            .unwrap_or_else(|| {
+                missing = Some(name.to_string());
+                String::new()
+            })
+    });
+    match missing {
+        Some(name) => Err(MyError::MissingSecret(name)),
+        None => Ok(resolved.into_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize() -> Result<(), MyError> {
+        let json = r#"
+{
+    "method": "POST",
+    "headers": {
+        "X-App-Token": "${APP_TOKEN}",
+        "Accept": "application/json"
+    },
+    "body": "{\"limit\": 1000}",
+    "auth": {
+        "type": "basic",
+        "login": "roadwork",
+        "password": "${PASSWORD}"
+    }
+}
+        "#;
+        let request = serde_json::from_str::<Request>(json)?;
+        assert_eq!(request.method, Method::Post);
+        assert_eq!(request.headers.len(), 2);
+        assert_eq!(request.body, Some("{\"limit\": 1000}".to_string()));
+        assert_eq!(
+            request.auth,
+            Some(Auth::Basic {
+                login: "roadwork".to_string(),
+                password: "${PASSWORD}".to_string()
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_headers() -> Result<(), MyError> {
+        let request = Request {
+            headers: HashMap::from([("X-App-Token".to_string(), "${APP_TOKEN}".to_string())]),
+            auth: Some(Auth::Bearer {
+                token: "${TOKEN}".to_string(),
+            }),
+            ..Request::default()
+        };
+        let secrets = HashMap::from([
+            ("APP_TOKEN".to_string(), "app".to_string()),
+            ("TOKEN".to_string(), "secret".to_string()),
+        ]);
+        let headers = request.resolve_headers(&secrets)?;
+        assert_eq!(
+            headers,
+            vec![
+                ("X-App-Token".to_string(), "app".to_string()),
+                ("Authorization".to_string(), "Bearer secret".to_string())
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_missing_secret() {
+        let result = resolve("${ROADWORK_UNDEFINED_SECRET}", &HashMap::new());
+        assert!(
+            matches!(result, Err(MyError::MissingSecret(name)) if name == "ROADWORK_UNDEFINED_SECRET")
+        );
+    }
+}
@@ -1,11 +1,15 @@
+use crate::opendata::json::model::archive::Archive;
 use crate::opendata::json::model::date_parser::DateParser;
+use crate::opendata::json::model::format::Format;
+use crate::opendata::json::model::id_descriptor::IdDescriptor;
 use crate::opendata::json::model::metadata::Metadata;
+use crate::opendata::json::model::request::Request;
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct ServiceDescriptor {
     pub(crate) metadata: Metadata,
-    pub(crate) id: String,
+    pub(crate) id: IdDescriptor,
     pub(crate) latitude: Option<String>,
     pub(crate) longitude: Option<String>,
     pub(crate) polygon: Option<String>,
@@ -20,6 +24,10 @@ pub(crate) struct ServiceDescriptor {
     #[serde(rename = "roadworkArray")]
     pub(crate) roadwork_array: String,
     pub(crate) url: Option<String>,
+    pub(crate) request: Option<Request>,
+    pub(crate) archive: Option<Archive>,
+    #[serde(default)]
+    pub(crate) format: Format,
 }
 
 #[cfg(test)]
@@ -33,28 +41,31 @@ mod tests {
         let service_descriptor = serde_json::from_reader::<File, ServiceDescriptor>(file)?;
 
         // Vérifier les champs de navigation JSON
-        assert_eq!(service_descriptor.roadwork_array, "$.records");
-        assert_eq!(service_descriptor.id, "@.recordid");
+        assert_eq!(service_descriptor.roadwork_array, "$.records[*]");
+        assert_eq!(
+            service_descriptor.id,
+            IdDescriptor::Path("$.recordid".to_string())
+        );
         assert_eq!(
             service_descriptor.latitude,
-            Some("@.geometry.coordinates[1]".to_string())
+            Some("$.geometry.coordinates[1]".to_string())
         );
         assert_eq!(
             service_descriptor.longitude,
-            Some("@.geometry.coordinates[0]".to_string())
+            Some("$.geometry.coordinates[0]".to_string())
         );
         assert_eq!(
             service_descriptor.polygon,
-            Some("@.fields.geo_shape.coordinates".to_string())
+            Some("$.fields.geo_shape.coordinates[0]".to_string())
         );
-        assert_eq!(service_descriptor.road, Some("@.fields.voie".to_string()));
+        assert_eq!(service_descriptor.road, Some("$.fields.voie".to_string()));
         assert_eq!(
             service_descriptor.location_details,
-            Some("@.fields.precision_localisation".to_string())
+            Some("$.fields.precision_localisation".to_string())
         );
         assert_eq!(
             service_descriptor.impact_circulation_detail,
-            Some("@.fields.impact_circulation_detail".to_string())
+            Some("$.fields.impact_circulation_detail".to_string())
         );
 
         // Vérifier que les champs optionnels sont correctement définis
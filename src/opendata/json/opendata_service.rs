@@ -2,92 +2,236 @@ use crate::MyError;
 use crate::MyError::RoadworkParsingError;
 use crate::json_tools::JsonTools;
 use crate::model::date_range::DateRange;
+use crate::model::parse_diagnostic::ParseDiagnostic;
 use crate::model::roadwork::Roadwork;
 use crate::model::roadwork_data::RoadworkData;
+use crate::model::validators::Validators;
 use crate::opendata::json::model::date_parser::DateParser;
 use crate::opendata::json::model::date_result::DateResult;
+use crate::opendata::json::model::request;
 use crate::opendata::json::model::service_descriptor::ServiceDescriptor;
 use crate::service::http_service::HttpService;
 use chrono::{DateTime, Datelike, Timelike};
 use chrono_tz::Tz;
 use jsonpath_rust::JsonPath;
 use log::{error, info, warn};
+use reqwest::header::{ETAG, HeaderName, LAST_MODIFIED};
+use reqwest::{StatusCode, Url};
 use serde_json::Value;
-use std::fs;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use unicode_normalization::UnicodeNormalization;
+
+/// The body of a response with its validators
+type Response = (Box<dyn Read>, Validators);
+
+/// The result of a call to an opendata service
+#[derive(Debug)]
+pub(crate) enum Fetched {
+    Data(RoadworkData),
+    /// The service answered that the data did not change since the validators were received
+    NotModified,
+}
 
 #[derive(Debug)]
 pub(crate) struct OpendataService {
-    service_name: String,
-    http_service: HttpService,
+    pub(crate) service_name: String,
+    http_service: Arc<HttpService>,
     pub(crate) service_descriptor: ServiceDescriptor,
+    /// When defined, the data is read from a saved response in this folder instead of the service url
+    fixtures: Option<PathBuf>,
 }
 
 impl OpendataService {
-    pub(crate) fn new(service_name: String, service_descriptor: ServiceDescriptor) -> Self {
+    pub(crate) fn new(
+        service_name: String,
+        service_descriptor: ServiceDescriptor,
+        http_service: Arc<HttpService>,
+    ) -> Self {
         Self {
             service_name,
             service_descriptor,
-            http_service: HttpService,
+            http_service,
+            fixtures: None,
         }
     }
+
+    pub(crate) fn with_fixtures(mut self, fixtures: Option<PathBuf>) -> Self {
+        self.fixtures = fixtures;
+        self
+    }
 }
 
 impl OpendataService {
-    pub(crate) fn get_data(&self) -> Result<RoadworkData, MyError> {
-        let url = self.build_url();
-        info!("getData {url}");
-        let json = if cfg!(debug_assertions) {
-            fs::read_to_string("test/example.json").expect("Unable to read file")
-        } else {
-            self.http_service.get_url(&url)?
+    /// Retrieve the data from the opendata service.
+    /// The secrets are used to resolve the placeholders of the url and of the request,
+    /// the validators of the previous response make the request conditional
+    pub(crate) fn get_data(
+        &self,
+        secrets: &HashMap<String, String>,
+        validators: &Validators,
+    ) -> Result<Fetched, MyError> {
+        let (mut reader, validators) = match &self.fixtures {
+            Some(fixtures) => (self.open_fixture(fixtures)?, Validators::default()),
+            None => {
+                info!("getData {}", self.service_descriptor.metadata.url);
+                match self.fetch(&self.build_url(secrets)?, secrets, validators)? {
+                    Some(fetched) => fetched,
+                    None => return Ok(Fetched::NotModified),
+                }
+            }
         };
-        self.parse_json(&json)
+        if let Some(archive) = &self.service_descriptor.archive {
+            reader = archive.extract(reader)?;
+        }
+        let json = self.service_descriptor.format.read(reader)?;
+        let mut roadwork_data = self.parse_json(&json)?;
+        roadwork_data.validators = validators;
+        Ok(Fetched::Data(roadwork_data))
+    }
+
+    /// Open the saved response of the service, it is the file of the fixtures folder named after the service
+    fn open_fixture(&self, fixtures: &Path) -> Result<Box<dyn Read>, MyError> {
+        let fixture = fs::read_dir(fixtures)?
+            .flatten()
+            .map(|entry| entry.path())
+            .find(|path| {
+                path.is_file()
+                    && path
+                        .file_stem()
+                        .and_then(|stem| stem.to_str())
+                        .is_some_and(|stem| stem.nfc().collect::<String>() == self.service_name)
+            })
+            .ok_or_else(|| {
+                MyError::ParsingError(format!(
+                    "No fixture for {} in {}",
+                    self.service_name,
+                    fixtures.display()
+                ))
+            })?;
+        info!("getData from fixture {fixture:?}");
+        Ok(Box::new(File::open(fixture)?))
     }
 
-    fn parse_json(&self, json: &str) -> Result<RoadworkData, MyError> {
-        let json: serde_json::Value = serde_json::from_str(json)?;
+    /// Returns the response with its validators, None if the service answered 304 Not Modified
+    fn fetch(
+        &self,
+        url: &str,
+        secrets: &HashMap<String, String>,
+        validators: &Validators,
+    ) -> Result<Option<Response>, MyError> {
+        if url.starts_with("file:") {
+            let path = Url::parse(url)
+                .ok()
+                .and_then(|url| url.to_file_path().ok())
+                .ok_or_else(|| MyError::ParsingError(format!("Invalid file url {url}")))?;
+            return Ok(Some((Box::new(File::open(path)?), Validators::default())));
+        }
+        let mut headers = validators.headers();
+        let response = match &self.service_descriptor.request {
+            None => self
+                .http_service
+                .send(reqwest::Method::GET, url, &headers, None)?,
+            Some(request) => {
+                headers.extend(request.resolve_headers(secrets)?);
+                self.http_service.send(
+                    request.method.into(),
+                    url,
+                    &headers,
+                    request.resolve_body(secrets)?,
+                )?
+            }
+        };
+        if response.status() == StatusCode::NOT_MODIFIED {
+            info!("{} is not modified", self.service_name);
+            return Ok(None);
+        }
+        let header = |name: HeaderName| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let validators = Validators {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        };
+        Ok(Some((Box::new(response), validators)))
+    }
+
+    fn parse_json(&self, json: &Value) -> Result<RoadworkData, MyError> {
         let roadwork_array = json.query(&self.service_descriptor.roadwork_array)?;
         info!("Found {} roadworks", roadwork_array.len());
         let mut roadworks = Vec::with_capacity(roadwork_array.len());
-        for value in roadwork_array {
-            match self.build_roadwork(value) {
+        let mut diagnostics = Vec::new();
+        for (index, value) in roadwork_array.into_iter().enumerate() {
+            let mut warnings = Vec::new();
+            let result = self
+                .build_roadwork(value, &mut warnings)
+                .and_then(|roadwork| Self::validate(roadwork, &self.service_descriptor));
+            match result {
                 Ok(roadwork) => {
-                    if Self::is_valid(&roadwork) {
-                        roadworks.push(roadwork);
-                    } else {
-                        warn!("{roadwork:?} is invalid");
-                    }
+                    diagnostics.extend(warnings.into_iter().map(|warning| {
+                        warning.into_diagnostic(index, Some(roadwork.id.clone()), value, false)
+                    }));
+                    roadworks.push(roadwork);
+                }
+                Err(e) => {
+                    warn!("Unable to build roadwork {}", e.error);
+                    let record_id = self.service_descriptor.id.build(value).ok();
+                    diagnostics.push(e.into_diagnostic(index, record_id, value, true));
                 }
-                Err(e) => warn!("Unable to build roadwork {}", e),
             }
         }
-        Ok(RoadworkData::new(&self.service_name, roadworks))
+        let mut roadwork_data = RoadworkData::new(&self.service_name, roadworks);
+        roadwork_data.diagnostics = diagnostics;
+        let rejected_count = roadwork_data.rejected_count();
+        if rejected_count > 0 {
+            warn!(
+                "{rejected_count} records of {} were rejected",
+                roadwork_data.roadworks.len() + rejected_count
+            );
+        }
+        Ok(roadwork_data)
     }
 
-    fn build_url(&self) -> String {
+    fn build_url(&self, secrets: &HashMap<String, String>) -> Result<String, MyError> {
         let metadata = &self.service_descriptor.metadata;
+        let url = request::resolve(&metadata.url, secrets)?;
 
         match &metadata.url_params {
-            None => metadata.url.clone(),
+            None => Ok(url),
             Some(url_params) => {
-                let query_string = url_params
-                    .iter()
-                    .map(|(key, value)| format!("{key}={}", urlencoding::encode(value)))
-                    .reduce(|acc, s| format!("{acc}&{s}"))
-                    .unwrap_or_default();
-                if metadata.url.contains("?") {
-                    format!("{}&{}", &metadata.url, query_string)
+                let mut params = Vec::with_capacity(url_params.len());
+                for (key, value) in url_params {
+                    let value = request::resolve(value, secrets)?;
+                    params.push(format!("{key}={}", urlencoding::encode(&value)));
+                }
+                let query_string = params.join("&");
+                if url.contains("?") {
+                    Ok(format!("{url}&{query_string}"))
                 } else {
-                    format!("{}?{}", &metadata.url, query_string)
+                    Ok(format!("{url}?{query_string}"))
                 }
             }
         }
     }
 
-    fn is_valid(roadwork: &Roadwork) -> bool {
+    fn validate(
+        roadwork: Roadwork,
+        descriptor: &ServiceDescriptor,
+    ) -> Result<Roadwork, FieldError> {
         if roadwork.longitude == 0.0 && roadwork.latitude == 00.0 {
             warn!("{roadwork:?} is invalid because it has no location");
-            return false;
+            return Err(FieldError::new(
+                "location",
+                descriptor.latitude.as_deref(),
+                RoadworkParsingError("The roadwork has no location".to_string()),
+            ));
         }
         //        if (roadwork.getStart() == 0) {
         //            logger.warn("{} is invalid because it's start date is 0", roadwork);
@@ -97,92 +241,74 @@ impl OpendataService {
         //            logger.warn("{} is invalid because it's end date is 0", roadwork);
         //            return false;
         //        }
-        true
+        Ok(roadwork)
     }
 
-    fn build_roadwork(&self, node: &Value) -> Result<Roadwork, MyError> {
+    /// Build a roadwork from a record.
+    /// The problems that do not reject the record are added to the warnings
+    fn build_roadwork(
+        &self,
+        node: &Value,
+        warnings: &mut Vec<FieldError>,
+    ) -> Result<Roadwork, FieldError> {
+        let descriptor = &self.service_descriptor;
         let mut roadwork_builder = Roadwork {
-            id: node.get_path(&self.service_descriptor.id)?,
+            id: descriptor
+                .id
+                .build(node)
+                .map_err(|e| FieldError::new("id", Some(&descriptor.id.path()), e))?,
             ..Roadwork::default()
         };
-        let latitude_path = match &self.service_descriptor.latitude {
-            None => {
-                return Err(RoadworkParsingError(format!(
-                    "Unable to get latitude from {}",
-                    node
-                )));
-            }
-            Some(latitude_path) => {
-                if latitude_path.is_empty() {
-                    return Err(RoadworkParsingError(format!(
-                        "Unable to get latitude from {}",
-                        node
-                    )));
-                } else {
-                    Some(node.get_path_as_double(latitude_path)?)
-                }
-            }
-        };
-        if let Some(latitude_path) = latitude_path {
-            roadwork_builder.latitude = latitude_path;
-        } else {
-            warn!("Unable to get latitude as it's path is empty");
-        }
-        let longitude_path = match &self.service_descriptor.longitude {
-            None => {
-                return Err(RoadworkParsingError(format!(
-                    "Unable to get latitude from {}",
-                    node
-                )));
-            }
-            Some(longitude_path) => {
-                if longitude_path.is_empty() {
-                    return Err(RoadworkParsingError(format!(
-                        "Unable to get latitude from {}",
-                        node
-                    )));
-                } else {
-                    Some(node.get_path_as_double(longitude_path)?)
-                }
-            }
-        };
-        if let Some(longitude_path) = longitude_path {
-            roadwork_builder.longitude = longitude_path;
-        } else {
-            warn!("Unable to get longitude as it's path is empty");
-        }
-        if let Some(polygon_path) = &self.service_descriptor.polygon
+        roadwork_builder.latitude =
+            Self::get_coordinate(node, "latitude", descriptor.latitude.as_deref())?;
+        roadwork_builder.longitude =
+            Self::get_coordinate(node, "longitude", descriptor.longitude.as_deref())?;
+        if let Some(polygon_path) = &descriptor.polygon
             && !polygon_path.is_empty()
         {
             roadwork_builder.polygons = node.get_path_as_polygons(polygon_path);
         }
-        if let Some(road) = &self.service_descriptor.road {
+        if let Some(road) = &descriptor.road {
             roadwork_builder.road = node.get_path(road).ok();
         }
-        if let Some(description) = &self.service_descriptor.description {
+        if let Some(description) = &descriptor.description {
             roadwork_builder.description = node.get_path(description).ok();
         }
 
-        if let Some(location_details) = &self.service_descriptor.location_details {
+        if let Some(location_details) = &descriptor.location_details {
             roadwork_builder.location_details = node.get_path(location_details).ok();
         }
-        let date_range = self.get_date_range(node)?;
+        let date_range = self.get_date_range(node, warnings)?;
         roadwork_builder.start = date_range.from.timestamp_millis();
         roadwork_builder.end = date_range
             .to
             .map(|date| date.timestamp_millis())
             .unwrap_or(0);
-        if let Some(impact_circulation_detail) = &self.service_descriptor.impact_circulation_detail
-        {
+        if let Some(impact_circulation_detail) = &descriptor.impact_circulation_detail {
             roadwork_builder.impact_circulation_detail =
                 node.get_path(impact_circulation_detail).ok();
         }
-        if let Some(url) = &self.service_descriptor.url {
-            roadwork_builder.url = node.get_path(url)?;
+        if let Some(url) = &descriptor.url {
+            roadwork_builder.url = node
+                .get_path(url)
+                .map_err(|e| FieldError::new("url", Some(url), e))?;
         }
         Ok(roadwork_builder)
     }
 
+    fn get_coordinate(node: &Value, field: &str, path: Option<&str>) -> Result<f64, FieldError> {
+        match path {
+            Some(path) if !path.is_empty() => node
+                .get_path_as_double(path)
+                .map_err(|e| FieldError::new(field, Some(path), e)),
+            _ => Err(FieldError::new(
+                field,
+                None,
+                RoadworkParsingError(format!("Unable to get {field} as it's path is empty")),
+            )),
+        }
+    }
+
     fn parse_date(
         &self,
         node: &Value,
@@ -227,13 +353,20 @@ impl OpendataService {
             .with_nanosecond(0)
     }
 
-    fn get_date_range(&self, node: &Value) -> Result<DateRange, MyError> {
+    fn get_date_range(
+        &self,
+        node: &Value,
+        warnings: &mut Vec<FieldError>,
+    ) -> Result<DateRange, FieldError> {
         let current_year = chrono::Local::now().year();
+        let from = &self.service_descriptor.from;
         let start_time = self
-            .parse_date(node, &self.service_descriptor.from)
+            .parse_date(node, from)
             .map(|date_result| date_result.date)
-            .inspect_err(|e| error!("Error parsing start date {}", e))?;
-        match self.parse_date(node, &self.service_descriptor.to) {
+            .inspect_err(|e| error!("Error parsing start date {}", e))
+            .map_err(|e| FieldError::new("from", from.as_ref().map(|p| p.path.as_str()), e))?;
+        let to = &self.service_descriptor.to;
+        match self.parse_date(node, to) {
             Ok(end) => {
                 let mut end_date = end.date;
                 if end.add_year {
@@ -252,8 +385,165 @@ impl OpendataService {
             }
             Err(e) => {
                 error!("Error parsing end date {}", e);
+                warnings.push(FieldError::new(
+                    "to",
+                    to.as_ref().map(|p| p.path.as_str()),
+                    e,
+                ));
                 Ok(DateRange::without_end(start_time))
             }
         }
     }
 }
+
+/// An error on a field of a record, it is converted to a diagnostic
+struct FieldError {
+    field: String,
+    path: Option<String>,
+    error: MyError,
+}
+
+impl FieldError {
+    fn new(field: &str, path: Option<&str>, error: MyError) -> Self {
+        Self {
+            field: field.to_string(),
+            path: path.map(str::to_string),
+            error,
+        }
+    }
+
+    fn into_diagnostic(
+        self,
+        index: usize,
+        record_id: Option<String>,
+        record: &Value,
+        rejected: bool,
+    ) -> ParseDiagnostic {
+        let raw_value = self.path.as_ref().and_then(|path| {
+            record
+                .query(path)
+                .ok()
+                .and_then(|values| values.first().map(|value| value.to_string()))
+        });
+        ParseDiagnostic {
+            index,
+            record_id,
+            field: self.field,
+            path: self.path,
+            raw_value,
+            error: self.error.to_string(),
+            rejected,
+            record: serde_json::to_string_pretty(record).unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn service() -> OpendataService {
+        let descriptor = json!({
+            "metadata": {
+                "country": "France",
+                "name": "Test",
+                "sourceUrl": "https://example.com",
+                "url": "https://example.com/roadworks.json",
+                "center": {"lat": 48.85337, "lon": 2.34847}
+            },
+            "roadworkArray": "$.records[*]",
+            "id": "$.id",
+            "latitude": "$.lat",
+            "longitude": "$.lon",
+            "from": {"path": "$.from", "parsers": [{"matcher": ".*", "format": "%Y-%m-%d"}]},
+            "to": {"path": "$.to", "parsers": [{"matcher": ".*", "format": "%Y-%m-%d"}]}
+        });
+        OpendataService::new(
+            "Test".to_string(),
+            serde_json::from_value(descriptor).unwrap(),
+            Arc::default(),
+        )
+    }
+
+    #[test]
+    fn test_parse_json_diagnostics() -> Result<(), MyError> {
+        let json = json!({"records": [
+            {"id": "1", "lat": 48.85, "lon": 2.34, "from": "2025-02-10", "to": "2025-03-10"},
+            {"id": "2", "lat": 48.85, "lon": 2.34, "from": "10/02/2025", "to": "2025-03-10"},
+            {"id": "3", "lat": 48.85, "lon": 2.34, "from": "2025-02-10", "to": "soon"}
+        ]});
+        let roadwork_data = service().parse_json(&json)?;
+        assert_eq!(roadwork_data.roadworks.len(), 2);
+        assert_eq!(roadwork_data.rejected_count(), 1);
+        assert_eq!(roadwork_data.diagnostics.len(), 2);
+
+        let rejected = &roadwork_data.diagnostics[0];
+        assert!(rejected.rejected);
+        assert_eq!(rejected.index, 1);
+        assert_eq!(rejected.record_id, Some("2".to_string()));
+        assert_eq!(rejected.field, "from");
+        assert_eq!(rejected.path, Some("$.from".to_string()));
+        assert_eq!(rejected.raw_value, Some("\"10/02/2025\"".to_string()));
+
+        let warning = &roadwork_data.diagnostics[1];
+        assert!(!warning.rejected);
+        assert_eq!(warning.record_id, Some("3".to_string()));
+        assert_eq!(warning.field, "to");
+        assert_eq!(roadwork_data.roadworks["3"].end, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_file_url() -> Result<(), Box<dyn std::error::Error>> {
+        let path = fs::canonicalize("opendata/fixtures/France-Paris.json")?;
+        let url = Url::from_file_path(&path).unwrap();
+        let service = service();
+        let Some((mut reader, _)) =
+            service.fetch(url.as_str(), &HashMap::new(), &Validators::default())?
+        else {
+            panic!("a file is always modified");
+        };
+        let mut content = String::new();
+        reader.read_to_string(&mut content)?;
+        assert_eq!(content, fs::read_to_string(&path)?);
+        assert!(
+            service
+                .fetch("file:relative", &HashMap::new(), &Validators::default())
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_bundled_descriptors_with_fixtures() -> Result<(), Box<dyn std::error::Error>> {
+        let fixtures = Path::new("opendata/fixtures");
+        let mut failures = Vec::new();
+        for entry in fs::read_dir("opendata/json")? {
+            let path = entry?.path();
+            let name = path.file_stem().unwrap().to_string_lossy().to_string();
+            if name == "index" {
+                continue;
+            }
+            // every bundled descriptor must be valid and have a fixture
+            let descriptor: ServiceDescriptor = serde_json::from_reader(File::open(&path)?)?;
+            let service = OpendataService::new(name.clone(), descriptor, Arc::default())
+                .with_fixtures(Some(fixtures.to_path_buf()));
+            match service.get_data(&HashMap::new(), &Validators::default()) {
+                Ok(Fetched::Data(roadwork_data)) if !roadwork_data.diagnostics.is_empty() => {
+                    failures.extend(roadwork_data.diagnostics.iter().map(|diagnostic| {
+                        format!("{name}: {} {}", diagnostic.field, diagnostic.error)
+                    }))
+                }
+                Ok(Fetched::Data(roadwork_data)) if roadwork_data.roadworks.is_empty() => {
+                    failures.push(format!("{name} has no roadwork"))
+                }
+                Ok(Fetched::Data(_)) => {}
+                Ok(Fetched::NotModified) => failures.push(format!("{name} is not modified")),
+                Err(e) => failures.push(format!("{name}: {e}")),
+            }
+        }
+        assert!(failures.is_empty(), "{failures:#?}");
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
 pub(crate) mod bootstrap;
+pub(crate) mod data_loader;
 pub(crate) mod json;
 pub(crate) mod open_data_service_manager;
@@ -1,11 +1,16 @@
+use crate::MyError;
 use crate::model::roadwork_data::RoadworkData;
+use crate::model::roadwork_history::RoadworkHistory;
+use crate::model::validators::Validators;
+use crate::opendata::data_loader::Progress;
 use crate::opendata::json::model::lat_lng::LatLng;
 use crate::opendata::json::model::service_descriptor::ServiceDescriptor;
-use crate::opendata::json::opendata_service::OpendataService;
+use crate::opendata::json::opendata_service::{Fetched, OpendataService};
+use crate::service::http_service::HttpService;
 use crate::service::synchronization_service::SynchronizationService;
 use crate::settings::Settings;
+use crate::store::{FetchRun, RoadworkStore};
 use log::{debug, error, info};
-use roadwork_sync::SyncData;
 use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
@@ -19,15 +24,44 @@ pub(crate) struct OpenDataServiceManager {
     service_names: Vec<String>,
     opendata_services: HashMap<String, OpendataService>,
     synchronization_service: SynchronizationService,
+    store: Box<dyn RoadworkStore>,
 }
 
 impl OpenDataServiceManager {
-    const VERSION: &'static str = "2";
+    /// The refresh interval used when neither the user nor the descriptor defines one
+    const DEFAULT_REFRESH_INTERVAL: u64 = 86400;
 
-    pub(crate) fn new(settings: Arc<Mutex<Settings>>) -> Self {
-        let opendata_services = Self::get_json_file_names(&crate::opendata_folder_path());
+    /// Create the manager, if fixtures is defined the services read their saved response
+    /// in this folder instead of calling their url.
+    /// The data read from the fixtures is then kept apart from the real caches and is not synchronized
+    pub(crate) fn new(
+        settings: Arc<Mutex<Settings>>,
+        fixtures: Option<PathBuf>,
+        http_service: Arc<HttpService>,
+    ) -> Self {
+        let opendata_services = Self::get_json_file_names(
+            &crate::opendata_folder_path(),
+            fixtures.as_deref(),
+            &http_service,
+        );
+        let settings_folder = Settings::settings_folder();
+        let mut store_folder = settings_folder
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(".roadwork"));
+        if fixtures.is_some() {
+            store_folder.push("offline");
+        }
+        let store = crate::store::open(&settings.lock().unwrap(), store_folder);
+        let synchronization_service = match fixtures {
+            None => {
+                SynchronizationService::new(Arc::clone(&settings), http_service, settings_folder)
+            }
+            Some(_) => SynchronizationService::new(Arc::clone(&settings), http_service, None)
+                .with_offline(true),
+        };
         Self {
-            synchronization_service: SynchronizationService::new(Arc::clone(&settings)),
+            store,
+            synchronization_service,
             settings,
             service_names: opendata_services.keys().map(|s| s.to_string()).collect(),
             opendata_services,
@@ -42,126 +76,250 @@ impl OpenDataServiceManager {
             .unwrap_or_default()
     }
 
-    pub(crate) fn get_data(&self) -> Option<RoadworkData> {
-        let mut roadworks_option = self.get_roadworks();
+    /// Returns the data of the service, it is fetched again if it is older than the refresh
+    /// interval or if force is true, the cached data is kept if the fetch fails.
+    /// The steps are reported to the progress, None is returned if it is cancelled
+    pub(crate) fn load_data(
+        &self,
+        source: &str,
+        force: bool,
+        progress: &Progress,
+    ) -> Option<RoadworkData> {
+        let mut roadworks_option = self.get_roadworks(source, force, progress);
+        if progress.is_cancelled() {
+            return None;
+        }
         if let Some(roadwork_data) = &mut roadworks_option {
             Self::apply_finished_status(roadwork_data);
-            self.synchronization_service.synchronize(roadwork_data);
+            progress.set_step("Synchronizing");
+            // the failure is kept in the synchronization state shown by the UI
+            self.synchronization_service.synchronize(roadwork_data).ok();
         }
         roadworks_option
     }
 
+    pub(crate) fn synchronization_service(&self) -> &SynchronizationService {
+        &self.synchronization_service
+    }
+
     /// Save the roadwork state
     pub(crate) fn save(&self, roadwork_data: &RoadworkData) {
         info!("save {}", roadwork_data.source);
-        if let Some(save_path) = self.get_path(&roadwork_data.source) {
-            info!("save to {save_path:?}");
-            match save_path.parent() {
-                None => error!(
-                    "Unable to save cache to {save_path:?} because parent directory does not exist"
-                ),
-                Some(parent) => {
-                    fs::create_dir_all(parent).ok();
-                    match File::create(&save_path) {
-                        Ok(file) => {
-                            if let Err(e) = serde_json::to_writer_pretty(file, roadwork_data) {
-                                error!("Unable to save cache to {save_path:?} because {e}");
-                            }
-                        }
-                        Err(e) => error!("Unable to save cache to {save_path:?} because {e}"),
-                    }
-                }
+        if let Err(e) = self.store.save(roadwork_data) {
+            error!("Unable to save {} because {e}", roadwork_data.source);
+        }
+    }
+
+    /// Save the synchronization state of roadworks changed by the user
+    pub(crate) fn save_sync_data(&self, roadwork_data: &RoadworkData, ids: &[&str]) {
+        if let Err(e) = self.store.save_sync_data(roadwork_data, ids) {
+            error!(
+                "Unable to save the changes of {} because {e}",
+                roadwork_data.source
+            );
+        }
+    }
+
+    /// Returns the roadworks that disappeared from the service
+    pub(crate) fn load_history(&self, source: &str) -> RoadworkHistory {
+        self.store
+            .load_history(source)
+            .inspect_err(|e| error!("Unable to load the history of {source} because {e}"))
+            .unwrap_or_else(|_| RoadworkHistory::new(source))
+    }
+
+    fn save_history(&self, history: &RoadworkHistory) {
+        if let Err(e) = self.store.save_history(history) {
+            error!(
+                "Unable to save the history of {} because {e}",
+                history.source
+            );
+        }
+    }
+
+    /// Export the history of the service as csv and returns the path of the file
+    pub(crate) fn export_history(&self, history: &RoadworkHistory) -> Result<PathBuf, MyError> {
+        let path = Settings::settings_folder()
+            .map(|mut folder| {
+                folder.push(format!("{}.history.csv", history.source));
+                folder
+            })
+            .ok_or_else(|| MyError::ParsingError("There is no settings folder".to_string()))?;
+        history.export_csv(&path)?;
+        Ok(path)
+    }
+
+    /// Merge the new data with the previous one.
+    /// The removed roadworks and the ones past their end date are archived in the history,
+    /// and if a roadwork from the history is back in the service it takes back its status
+    fn merge(&self, new_data: &mut RoadworkData, previous: RoadworkData) {
+        info!("reloaded {} new roadworks", new_data.roadworks.len());
+        let mut history = self.load_history(&new_data.source);
+        for roadwork in new_data.roadworks.values_mut() {
+            if !previous.roadworks.contains_key(&roadwork.id)
+                && let Some(archived) = history.restore(&roadwork.id)
+            {
+                info!("Roadwork {} is back in the service", roadwork.id);
+                roadwork.update_from(&archived);
             }
         }
+        history.archive(new_data.merge(previous));
+        history.archive_expired(new_data.iter());
+        self.save_history(&history);
+    }
+
+    /// Returns the refresh interval of the service,
+    /// the one of the user settings first, then the one of the descriptor
+    fn refresh_interval(&self, source: &str) -> Duration {
+        let from_settings = self
+            .settings
+            .lock()
+            .unwrap()
+            .refresh_intervals
+            .get(source)
+            .copied();
+        let seconds = from_settings
+            .or_else(|| {
+                self.opendata_services
+                    .get(source)
+                    .and_then(|ods| ods.service_descriptor.metadata.refresh_interval)
+            })
+            .unwrap_or(Self::DEFAULT_REFRESH_INTERVAL);
+        Duration::from_secs(seconds)
     }
 
     /**
      * Returns roadwork data.
-     * If
+     * The cache is used until it is older than the refresh interval, or if force is true.
+     * When the fetch fails the cache is returned marked as stale,
+     * and nothing is saved if the progress is cancelled during the fetch
      *
      * @return an optional that should contain Roadwork data
      */
-    fn get_roadworks(&self) -> Option<RoadworkData> {
-        let current_path = self.get_path(&self.settings.lock().unwrap().opendata_service);
-        if let Some(current_path) = &current_path {
-            info!("getData {current_path:?}");
-            match Self::load_cache(current_path) {
-                None => {
-                    info!("There is no cached data");
-                    self.get_opendata_service()
-                        .and_then(|ods| ods.get_data().ok())
-                        .inspect(|new_data| self.save(new_data))
+    fn get_roadworks(
+        &self,
+        source: &str,
+        force: bool,
+        progress: &Progress,
+    ) -> Option<RoadworkData> {
+        info!("getData {source}");
+        progress.set_step("Reading the cache");
+        let cached = match self.store.load(source) {
+            Ok(cached) => cached,
+            // Fetching would overwrite the data of the newer version with a fresh copy
+            Err(e @ MyError::UnsupportedVersion(..)) => {
+                error!("Unable to load the cache of {source} because {e}");
+                return None;
+            }
+            Err(e) => {
+                error!("Unable to load the cache of {source} because {e}");
+                None
+            }
+        };
+        match cached {
+            None => {
+                info!("There is no cached data");
+                let fetched = self.fetch(source, &Validators::default(), progress)?;
+                if progress.is_cancelled() {
+                    return None;
                 }
-                Some(mut cached_roadwork_data) => {
-                    if (cached_roadwork_data.created + Duration::from_secs(86400))
-                        .le(&SystemTime::now().duration_since(UNIX_EPOCH).unwrap())
-                    {
-                        info!("Cache is obsolete {current_path:?}");
-                        fs::remove_file(current_path).ok();
-                        let mut new_data_optional = self
-                            .get_opendata_service()
-                            .and_then(|ods| ods.get_data().ok());
-                        if let Some(new_data) = &mut new_data_optional {
-                            let new_roadworks = &mut new_data.roadworks;
-                            info!("reloaded {} new roadworks", new_roadworks.len());
-                            for existing_roadwork in &mut cached_roadwork_data {
-                                if let Some(new_roadwork) =
-                                    new_roadworks.get_mut(&existing_roadwork.id)
-                                {
-                                    new_roadwork.sync_data =
-                                        SyncData::new_from(&existing_roadwork.sync_data);
-                                    info!(
-                                        "Roadwork {} -> status {}",
-                                        existing_roadwork.id, existing_roadwork.sync_data.status
-                                    );
-                                }
-                            }
-                            self.save(new_data);
-                        }
-                        return new_data_optional;
+                match fetched {
+                    Fetched::Data(new_data) => {
+                        self.save(&new_data);
+                        Some(new_data)
+                    }
+                    Fetched::NotModified => None,
+                }
+            }
+            Some(cached_roadwork_data)
+                if !force && cached_roadwork_data.age() < self.refresh_interval(source) =>
+            {
+                Some(cached_roadwork_data)
+            }
+            Some(mut cached_roadwork_data) => {
+                info!("Cache is obsolete {source}");
+                let fetched = self.fetch(source, &cached_roadwork_data.validators, progress);
+                if progress.is_cancelled() {
+                    return None;
+                }
+                match fetched {
+                    Some(Fetched::Data(mut new_data)) => {
+                        self.merge(&mut new_data, cached_roadwork_data);
+                        self.save(&new_data);
+                        Some(new_data)
+                    }
+                    Some(Fetched::NotModified) => {
+                        cached_roadwork_data.created =
+                            SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+                        self.save(&cached_roadwork_data);
+                        Some(cached_roadwork_data)
+                    }
+                    None => {
+                        info!("Keeping the stale cache of {source}");
+                        cached_roadwork_data.stale = true;
+                        Some(cached_roadwork_data)
                     }
-
-                    Some(cached_roadwork_data)
                 }
             }
-        } else {
-            info!("There is no cached folder");
-            self.get_opendata_service()
-                .and_then(|ods| ods.get_data().ok())
-                .inspect(|new_data| self.save(new_data))
         }
     }
 
+    /// Fetch fresh data from the opendata service,
+    /// the validators of the cached data are sent to only download it if it changed
+    fn fetch(&self, source: &str, validators: &Validators, progress: &Progress) -> Option<Fetched> {
+        let ods = self.opendata_services.get(source)?;
+        progress.set_step(format!("Downloading {source}"));
+        let secrets = self.settings.lock().unwrap().secrets(source);
+        let result = ods.get_data(&secrets, validators);
+        let data = match &result {
+            Ok(Fetched::Data(data)) => Some(data),
+            _ => None,
+        };
+        let fetch_run = FetchRun {
+            source: ods.service_name.clone(),
+            time: SystemTime::now().duration_since(UNIX_EPOCH).unwrap(),
+            roadworks: data.map_or(0, |data| data.roadworks.len()),
+            rejected: data.map_or(0, |data| data.rejected_count()),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        };
+        if let Err(e) = self.store.record_fetch(&fetch_run) {
+            error!("Unable to record the fetch of {}: {e}", ods.service_name);
+        }
+        result
+            .inspect_err(|e| error!("Unable to get data from {}: {e}", ods.service_name))
+            .ok()
+    }
+
     pub(crate) fn get_opendata_service(&self) -> Option<&OpendataService> {
         debug!("get_opendata_service");
-        let opendata_service = &self.settings.lock().unwrap().opendata_service;
+        let opendata_service = self.settings.lock().unwrap().opendata_service.clone();
         debug!("opendata_service: {opendata_service}");
-        self.opendata_services.get(opendata_service)
-    }
-
-    fn get_path(&self, opendata_service: &str) -> Option<PathBuf> {
-        Settings::settings_folder().map(|mut folder| {
-            folder.push(format!("{opendata_service}.{}.json", Self::VERSION));
-            folder
-        })
+        self.get_service(&opendata_service)
     }
 
-    fn load_cache(cache_path: &Path) -> Option<RoadworkData> {
-        File::open(cache_path)
-            .ok()
-            .and_then(|file| serde_json::from_reader::<File, RoadworkData>(file).ok())
+    pub(crate) fn get_service(&self, source: &str) -> Option<&OpendataService> {
+        self.opendata_services.get(source)
     }
 
+    /// The expired roadworks are finished, it is a local change sent to the team
     fn apply_finished_status(roadwork_data: &mut RoadworkData) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
         for roadwork in roadwork_data.roadworks.values_mut() {
-            if roadwork.is_expired() {
-                roadwork.sync_data.status = roadwork_sync::Status::Finished;
+            if roadwork.is_expired() && roadwork.sync_data.status != roadwork_sync::Status::Finished
+            {
+                roadwork
+                    .sync_data
+                    .set_status(roadwork_sync::Status::Finished, now.as_millis() as u64);
             }
         }
     }
 
     /// Returns a vector of JSON file names without the `.json` extension from the given directory.
-    fn get_json_file_names(path: &std::path::Path) -> HashMap<String, OpendataService> {
+    fn get_json_file_names(
+        path: &std::path::Path,
+        fixtures: Option<&Path>,
+        http_service: &Arc<HttpService>,
+    ) -> HashMap<String, OpendataService> {
         info!("get_json_file_names {:?}", path);
         let mut services = HashMap::new();
         if let Ok(entries) = fs::read_dir(path) {
@@ -178,8 +336,12 @@ impl OpenDataServiceManager {
                             Ok(file) => {
                                 match serde_json::from_reader::<File, ServiceDescriptor>(file) {
                                     Ok(service_descriptor) => {
-                                        let opendata_service =
-                                            OpendataService::new(name.into(), service_descriptor);
+                                        let opendata_service = OpendataService::new(
+                                            name.into(),
+                                            service_descriptor,
+                                            Arc::clone(http_service),
+                                        )
+                                        .with_fixtures(fixtures.map(Path::to_path_buf));
                                         services.insert(name.to_string(), opendata_service);
                                     }
                                     Err(e) => error!("Failed to parse file {path:?}: {e}"),
@@ -194,14 +356,12 @@ impl OpenDataServiceManager {
         services
     }
 
-    pub(crate) fn services(&self) -> &[String] {
-        &self.service_names
+    /// Returns the storage problems that happened since the last call
+    pub(crate) fn take_warnings(&self) -> Vec<String> {
+        self.store.take_warnings()
     }
 
-    pub(crate) fn delete_cache(&self) {
-        if let Some(current_path) = self.get_path(&self.settings.lock().unwrap().opendata_service) {
-            info!("delete_cache {current_path:?}");
-            fs::remove_file(&current_path).ok();
-        }
+    pub(crate) fn services(&self) -> &[String] {
+        &self.service_names
     }
 }
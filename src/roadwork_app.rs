@@ -1,20 +1,41 @@
 use crate::gui::about_dialog::AboutDialog;
+use crate::gui::assignee_panel::AssigneePanel;
+use crate::gui::conflicts_dialog::{ConflictAction, ConflictsDialog};
+use crate::gui::diagnostics_panel::DiagnosticsPanel;
+use crate::gui::history_dialog::{HistoryAction, HistoryDialog};
+use crate::gui::legend::{Legend, source_color};
 use crate::gui::logs_panel::LogsPanel;
 use crate::gui::metada_dialog::MetadataDialog;
-use crate::gui::roadwork_marker::RoadworkMarker;
+use crate::gui::notes_panel::NotesPanel;
+use crate::gui::roadwork_marker::{Assignment, RoadworkMarker};
 use crate::gui::status_panel::StatusPanel;
+use crate::gui::sync_settings_dialog::{
+    ConnectionTest, SyncSettingsAction, SyncSettingsDialog, SyncSettingsForm,
+};
+use crate::model::roadwork::{Roadwork, format_age, format_date};
 use crate::model::roadwork_data::RoadworkData;
+use crate::model::roadwork_history::RoadworkHistory;
+use crate::opendata::data_loader::{DataLoader, LoadResult};
 use crate::opendata::json::model::lat_lng::LatLng;
 use crate::opendata::open_data_service_manager::OpenDataServiceManager;
+use crate::service::background_sync::{BackgroundSync, DEFAULT_SYNC_INTERVAL};
+use crate::service::http_service::HttpService;
+use crate::service::synchronization_service::SyncState;
 use crate::settings::Settings;
 use chrono::DateTime;
 use eframe::epaint::text::TextWrapMode;
 use eframe::{App, Frame, Storage};
 use egui::text::LayoutJob;
-use egui::{Button, Context, Label, Response, RichText, Ui}; // menu used in show_top_panel
+use egui::{Button, Color32, Context, Label, Response, RichText, Ui}; // menu used in show_top_panel
 use egui_notify::Toasts;
 use log::info;
-use std::sync::{Arc, Mutex};
+use roadwork_sync::{Conflict, SyncConfig};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread::JoinHandle;
+use std::time::Duration;
 use walkers::sources::OpenStreetMap;
 use walkers::{HttpOptions, HttpTiles, Map, MapMemory, Projector};
 
@@ -25,22 +46,50 @@ pub struct RoadworkApp {
     tiles: HttpTiles,
     map_memory: MapMemory,
     settings: Arc<Mutex<Settings>>,
-    open_data_service_manager: OpenDataServiceManager,
+    open_data_service_manager: Arc<OpenDataServiceManager>,
+    /// The loadings in progress by service, the previous data is shown meanwhile
+    loaders: BTreeMap<String, DataLoader>,
+    egui_ctx: Context,
     position: LatLng,
-    roadwork_data: Option<RoadworkData>,
-    selected_roadwork: Option<String>,
+    /// The data of the services shown on the map, by service
+    roadwork_data: BTreeMap<String, RoadworkData>,
+    /// The service and the id of the selected roadwork
+    selected_roadwork: Option<(String, String)>,
     logs_panel_open: bool,
+    diagnostics_panel_open: bool,
     toasts: Toasts,
     show_about_dialog: bool,
     show_info_dialog: bool,
+    /// The history of the current service, loaded when the history dialog is opened
+    history: Option<RoadworkHistory>,
+    show_history_dialog: bool,
+    /// The queued synchronizations being sent again
+    sync_retry: Option<JoinHandle<()>>,
+    /// The synchronization conflicts the user has to resolve, with the service of their roadwork
+    conflicts: Vec<(String, Conflict)>,
+    show_conflicts_dialog: bool,
+    background_sync: BackgroundSync,
+    show_sync_settings_dialog: bool,
+    /// The synchronization settings being edited
+    sync_settings_form: SyncSettingsForm,
+    connection_test: Option<ConnectionTest>,
+    connection_test_receiver: Option<Receiver<Result<(), String>>>,
+    /// The note being written on the selected roadwork
+    note_draft: String,
 }
 
 impl RoadworkApp {
-    pub fn new(egui_ctx: Context) -> Self {
-        // Ensure opendata descriptors are available when starting the app
-        crate::opendata::bootstrap::ensure_opendata_available();
-
+    /// Create the application.
+    /// If fixtures is defined, the app is offline and the opendata services are read from this folder
+    pub fn new(egui_ctx: Context, fixtures: Option<PathBuf>) -> Self {
         let settings: Arc<Mutex<Settings>> = Default::default();
+        let http_service = Arc::new(HttpService::from_settings(&settings.lock().unwrap().http));
+        match &fixtures {
+            // Ensure opendata descriptors are available when starting the app
+            None => crate::opendata::bootstrap::ensure_opendata_available(&http_service),
+            Some(fixtures) => info!("Offline mode, reading fixtures from {fixtures:?}"),
+        }
+
         let http_options = HttpOptions {
             cache: Settings::settings_folder().map(|mut settings_folder| {
                 settings_folder.push("cache");
@@ -53,17 +102,35 @@ impl RoadworkApp {
             s.map_center.unwrap_or_default()
         };
         let mut app = Self {
-            tiles: HttpTiles::with_options(OpenStreetMap, http_options, egui_ctx),
+            tiles: HttpTiles::with_options(OpenStreetMap, http_options, egui_ctx.clone()),
             map_memory: Default::default(),
-            open_data_service_manager: OpenDataServiceManager::new(Arc::clone(&settings)),
+            open_data_service_manager: Arc::new(OpenDataServiceManager::new(
+                Arc::clone(&settings),
+                fixtures,
+                http_service,
+            )),
+            loaders: BTreeMap::new(),
+            egui_ctx,
             settings,
             position,
-            roadwork_data: None,
+            roadwork_data: BTreeMap::new(),
             selected_roadwork: None,
             logs_panel_open: false,
+            diagnostics_panel_open: false,
             toasts: Toasts::default(),
             show_about_dialog: false,
             show_info_dialog: false,
+            history: None,
+            show_history_dialog: false,
+            sync_retry: None,
+            conflicts: Vec::new(),
+            show_conflicts_dialog: false,
+            background_sync: BackgroundSync::new(),
+            show_sync_settings_dialog: false,
+            sync_settings_form: SyncSettingsForm::default(),
+            connection_test: None,
+            connection_test_receiver: None,
+            note_draft: String::new(),
         };
         // Restore zoom level if available in settings
         if let Some(z) = app.settings.lock().unwrap().map_zoom {
@@ -79,8 +146,7 @@ impl RoadworkApp {
 
     fn reload_data(&mut self) {
         info!("reload data");
-        self.open_data_service_manager.delete_cache();
-        self.load_data();
+        self.start_loading(true);
     }
 
     pub fn load_data(&mut self) {
@@ -88,7 +154,378 @@ impl RoadworkApp {
         // Prefer the saved map center if available, otherwise use the service metadata center
         let saved_center = { self.settings.lock().unwrap().map_center };
         self.position = saved_center.unwrap_or_else(|| self.open_data_service_manager.get_center());
-        self.roadwork_data = self.open_data_service_manager.get_data();
+        self.start_loading(false);
+    }
+
+    /// Load the data of the services shown on the map in the background.
+    /// Unless forced, only the services not loaded yet are loaded.
+    /// The data is saved before it is replaced or dropped so the changes since the last save are kept
+    fn start_loading(&mut self, force: bool) {
+        let services = self.settings.lock().unwrap().displayed_services();
+        self.loaders.retain(|source, loader| {
+            let keep = !force && services.contains(source);
+            if !keep {
+                loader.cancel();
+            }
+            keep
+        });
+        let manager = &self.open_data_service_manager;
+        self.roadwork_data.retain(|source, roadwork_data| {
+            let shown = services.contains(source);
+            if !shown {
+                manager.save(roadwork_data);
+            }
+            shown
+        });
+        if self
+            .selected_roadwork
+            .as_ref()
+            .is_some_and(|(source, _)| !services.contains(source))
+        {
+            self.selected_roadwork = None;
+        }
+        for source in services {
+            if self.loaders.contains_key(&source) {
+                continue;
+            }
+            match self.roadwork_data.get(&source) {
+                // the worker merges the fetched data with the stored one
+                Some(roadwork_data) if force => self.open_data_service_manager.save(roadwork_data),
+                Some(_) => continue,
+                None => {}
+            }
+            let loader = DataLoader::start(
+                Arc::clone(&self.open_data_service_manager),
+                source.clone(),
+                force,
+                self.egui_ctx.clone(),
+            );
+            self.loaders.insert(source, loader);
+        }
+    }
+
+    fn cancel_loading(&mut self) {
+        for loader in std::mem::take(&mut self.loaders).into_values() {
+            loader.cancel();
+        }
+    }
+
+    /// Take the data of the services whose loading is done
+    fn poll_loaders(&mut self) {
+        let finished = self
+            .loaders
+            .iter()
+            .filter_map(|(source, loader)| loader.poll().map(|result| (source.clone(), result)))
+            .collect::<Vec<_>>();
+        if finished.is_empty() {
+            return;
+        }
+        self.show_store_warnings();
+        for (source, result) in finished {
+            let Some(loader) = self.loaders.remove(&source) else {
+                continue;
+            };
+            match result {
+                LoadResult::Loaded(Some(roadwork_data)) => {
+                    if loader.force && roadwork_data.stale {
+                        self.toasts.error(format!(
+                            "Unable to reload {source}, the previous data is kept"
+                        ));
+                    }
+                    self.roadwork_data.insert(source, roadwork_data);
+                    self.background_sync.synchronized();
+                }
+                LoadResult::Loaded(None) | LoadResult::Failed => {
+                    self.toasts
+                        .error(format!("Unable to load the data of {source}"));
+                }
+            }
+        }
+    }
+
+    /// Show the steps of the loadings in progress with a button to cancel them
+    fn show_loading(&mut self, ui: &mut Ui) {
+        if self.loaders.is_empty() {
+            return;
+        }
+        ui.spinner();
+        let steps = self
+            .loaders
+            .values()
+            .map(|loader| loader.progress().step())
+            .filter(|step| !step.is_empty())
+            .collect::<Vec<_>>();
+        ui.label(steps.join(", "));
+        if ui.button("Cancel").clicked() {
+            self.cancel_loading();
+        }
+        // the workers only ask for a repaint when they are done
+        ui.ctx().request_repaint_after(Duration::from_millis(200));
+    }
+
+    fn show_store_warnings(&mut self) {
+        for warning in self.open_data_service_manager.take_warnings() {
+            self.toasts.warning(warning);
+        }
+    }
+
+    /// Take the changes of the team received in the background, and start the next
+    /// synchronization when it is due
+    fn background_sync(&mut self, ctx: &Context) {
+        let synchronization_service = self.open_data_service_manager.synchronization_service();
+        // the queued changes are sent before the requests of the worker or by a retry
+        let mut responses = synchronization_service.take_received();
+        responses.extend(self.background_sync.poll().unwrap_or_default());
+        if !responses.is_empty() {
+            for (request, response) in responses {
+                // the service may have been hidden meanwhile
+                if let Some(roadwork_data) = self.roadwork_data.get_mut(&request.source) {
+                    synchronization_service.apply(roadwork_data, &request, response);
+                }
+            }
+        }
+        // a loading synchronizes the data it loads
+        if !synchronization_service.is_enabled()
+            || !self.loaders.is_empty()
+            || self.background_sync.is_running()
+        {
+            return;
+        }
+        let interval = self
+            .settings
+            .lock()
+            .unwrap()
+            .sync_interval
+            .unwrap_or(DEFAULT_SYNC_INTERVAL);
+        let wait = self
+            .background_sync
+            .next_sync(Duration::from_secs(interval));
+        if !wait.is_zero() {
+            ctx.request_repaint_after(wait);
+            return;
+        }
+        let requests = self
+            .roadwork_data
+            .values()
+            .map(RoadworkData::sync_request)
+            .collect();
+        self.background_sync.start(
+            Arc::clone(&self.open_data_service_manager),
+            requests,
+            self.egui_ctx.clone(),
+        );
+    }
+
+    /// Select the roadwork, the highlight of the changes of the team on the previous one is removed
+    fn select_roadwork(&mut self, selection: Option<(String, String)>) {
+        if let Some((source, id)) = &self.selected_roadwork
+            && let Some(roadwork) = self
+                .roadwork_data
+                .get_mut(source)
+                .and_then(|roadwork_data| roadwork_data.get_mut_roadwork(id))
+        {
+            roadwork.changed_by_team = false;
+        }
+        if selection != self.selected_roadwork {
+            self.note_draft.clear();
+        }
+        self.selected_roadwork = selection;
+    }
+
+    fn open_sync_settings_dialog(&mut self) {
+        let settings = self.settings.lock().unwrap();
+        self.sync_settings_form = SyncSettingsForm {
+            url: settings.synchronization_url.clone(),
+            team: settings.synchronization_team.clone(),
+            login: settings.synchronization_login.clone(),
+            password: self
+                .open_data_service_manager
+                .synchronization_service()
+                .password(),
+            enabled: settings.synchronization_enabled,
+        };
+        self.connection_test = None;
+        self.show_sync_settings_dialog = true;
+    }
+
+    fn show_sync_settings_dialog(&mut self, ctx: &Context) {
+        if let Some(receiver) = &self.connection_test_receiver
+            && let Ok(result) = receiver.try_recv()
+        {
+            self.connection_test = Some(match result {
+                Ok(()) => ConnectionTest::Succeeded,
+                Err(e) => ConnectionTest::Failed(e),
+            });
+            self.connection_test_receiver = None;
+        }
+        if !self.show_sync_settings_dialog {
+            return;
+        }
+        let synchronization_service = self.open_data_service_manager.synchronization_service();
+        let action = SyncSettingsDialog::new(
+            &mut self.show_sync_settings_dialog,
+            &mut self.sync_settings_form,
+            self.connection_test.as_ref(),
+            synchronization_service.state(),
+            synchronization_service.queued_count(),
+        )
+        .show(ctx);
+        match action {
+            Some(SyncSettingsAction::TestConnection) => self.test_connection(),
+            Some(SyncSettingsAction::Save) => self.save_sync_settings(),
+            None => {}
+        }
+    }
+
+    /// Send a request with the edited settings on a worker thread
+    fn test_connection(&mut self) {
+        let form = &self.sync_settings_form;
+        let config = SyncConfig {
+            url: form.url.clone(),
+            team: form.team.clone(),
+            login: form.login.clone(),
+            password: form.password.clone(),
+            token: None,
+        };
+        let source = self.settings.lock().unwrap().opendata_service.clone();
+        let manager = Arc::clone(&self.open_data_service_manager);
+        let egui_ctx = self.egui_ctx.clone();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = manager
+                .synchronization_service()
+                .test_connection(config, &source)
+                .map_err(|e| e.to_string());
+            sender.send(result).ok();
+            egui_ctx.request_repaint();
+        });
+        self.connection_test = Some(ConnectionTest::Running);
+        self.connection_test_receiver = Some(receiver);
+    }
+
+    fn save_sync_settings(&mut self) {
+        let form = self.sync_settings_form.clone();
+        let result = {
+            let mut settings = self.settings.lock().unwrap();
+            settings.synchronization_url = form.url;
+            settings.synchronization_team = form.team;
+            settings.synchronization_login = form.login;
+            settings.synchronization_enabled = form.enabled;
+            settings.save()
+        };
+        self.open_data_service_manager
+            .synchronization_service()
+            .set_password(form.password);
+        match result {
+            Ok(()) => self.toasts.success("Synchronization settings saved"),
+            Err(e) => self
+                .toasts
+                .error(format!("Unable to save the settings: {e}")),
+        };
+        self.show_sync_settings_dialog = false;
+    }
+
+    fn show_sync_errors(&mut self) {
+        let synchronization_service = self.open_data_service_manager.synchronization_service();
+        for error in synchronization_service.take_errors() {
+            self.toasts.error(error);
+        }
+        let conflicts = synchronization_service.take_conflicts();
+        if conflicts.is_empty() {
+            return;
+        }
+        self.toasts.warning(format!(
+            "{} roadworks were also changed by your team",
+            conflicts.len()
+        ));
+        for (source, conflict) in conflicts {
+            // a conflict found again replaces the previous one
+            self.conflicts
+                .retain(|(s, c)| *s != source || c.id != conflict.id);
+            self.conflicts.push((source, conflict));
+        }
+    }
+
+    fn show_conflicts_dialog(&mut self, ctx: &Context) {
+        if !self.show_conflicts_dialog {
+            return;
+        }
+        let Some(action) =
+            ConflictsDialog::new(&mut self.show_conflicts_dialog, &self.conflicts).show(ctx)
+        else {
+            return;
+        };
+        let index = match action {
+            ConflictAction::KeepLocal(index)
+            | ConflictAction::TakeServer(index)
+            | ConflictAction::Show(index) => index,
+        };
+        let (source, conflict) = &self.conflicts[index];
+        if let Some(roadwork) = self
+            .roadwork_data
+            .get_mut(source)
+            .and_then(|roadwork_data| roadwork_data.get_mut_roadwork(&conflict.id))
+        {
+            match action {
+                ConflictAction::KeepLocal(_) => roadwork.sync_data.keep_local(&conflict.server),
+                ConflictAction::TakeServer(_) => roadwork.sync_data.copy(&conflict.server),
+                ConflictAction::Show(_) => {
+                    self.position = LatLng {
+                        lat: roadwork.latitude,
+                        lon: roadwork.longitude,
+                    };
+                    self.map_memory.follow_my_position();
+                    let selection = Some((source.clone(), conflict.id.clone()));
+                    self.select_roadwork(selection);
+                }
+            }
+        }
+        if !matches!(action, ConflictAction::Show(_)) {
+            let (source, conflict) = self.conflicts.remove(index);
+            if let Some(roadwork_data) = self.roadwork_data.get(&source) {
+                self.open_data_service_manager
+                    .save_sync_data(roadwork_data, &[&conflict.id]);
+            }
+            // the kept local change is sent again like any other local change
+            self.background_sync.local_change();
+        }
+    }
+
+    /// Show the result of the last synchronization, with a button to send the queued changes
+    fn show_sync_state(&mut self, ui: &mut Ui) {
+        let synchronization_service = self.open_data_service_manager.synchronization_service();
+        let queued = synchronization_service.queued_count();
+        match synchronization_service.state() {
+            SyncState::Disabled => {}
+            SyncState::Synchronized(time) => {
+                ui.colored_label(Color32::DARK_GREEN, "Synchronized")
+                    .on_hover_text(format!("At {}", format_date(time.as_millis() as i64)));
+            }
+            SyncState::Failed { time, error } => {
+                ui.colored_label(Color32::RED, "Not synchronized")
+                    .on_hover_text(format!(
+                        "At {}: {error}\n{queued} changes waiting to be sent",
+                        format_date(time.as_millis() as i64)
+                    ));
+            }
+        }
+        if queued == 0 {
+            return;
+        }
+        if self
+            .sync_retry
+            .as_ref()
+            .is_some_and(|retry| !retry.is_finished())
+        {
+            ui.spinner();
+        } else if ui.button("Retry").clicked() {
+            let manager = Arc::clone(&self.open_data_service_manager);
+            let egui_ctx = self.egui_ctx.clone();
+            self.sync_retry = Some(std::thread::spawn(move || {
+                manager.synchronization_service().retry().ok();
+                egui_ctx.request_repaint();
+            }));
+        }
     }
 
     fn get_multiline_text(text: &String) -> LayoutJob {
@@ -110,19 +547,27 @@ impl RoadworkApp {
     }
 
     fn show_left_panel(&mut self, ctx: &Context) {
-        let url = self.get_wme_url_pattern();
-
-        if let Some((id, roadwork_data)) = self
-            .selected_roadwork
-            .as_ref()
-            .zip(self.roadwork_data.as_mut())
+        let Some((source, id)) = self.selected_roadwork.clone() else {
+            return;
+        };
+        let url = self.get_wme_url_pattern(&source);
+        let show_source = self.roadwork_data.len() > 1;
+        let author = self.settings.lock().unwrap().synchronization_login.clone();
+        let note_draft = &mut self.note_draft;
+        let mut local_change = false;
+        let mut reviewed = false;
+        // the roadwork may have disappeared when the data was reloaded
+        if let Some(roadwork) = self
+            .roadwork_data
+            .get_mut(&source)
+            .and_then(|roadwork_data| roadwork_data.roadworks.get_mut(&id))
         {
-            let roadwork = roadwork_data
-                .roadworks
-                .get_mut(id)
-                .expect("roadwork not found");
             egui::SidePanel::left("left_panel").show(ctx, |ui| {
                 ui.vertical(|ui| {
+                    if show_source {
+                        ui.label(RichText::new("Service:").strong());
+                        ui.label(&source);
+                    }
                     ui.label(RichText::new("Id:").strong());
                     ui.add(Label::new(&roadwork.id).wrap_mode(TextWrapMode::Truncate));
                     ui.horizontal(|ui| {
@@ -190,6 +635,10 @@ impl RoadworkApp {
                         ui.label(Self::get_multiline_text(text));
                     }
 
+                    if roadwork.is_modified() && Self::show_changes(ui, roadwork) {
+                        reviewed = true;
+                    }
+
                     if ui
                         .add_enabled(!roadwork.url.is_empty(), Button::new("Open URL"))
                         .clicked()
@@ -197,14 +646,69 @@ impl RoadworkApp {
                         open::that(&roadwork.url).expect("failed to open url");
                     }
 
-                    StatusPanel::new(roadwork).show(ui);
+                    if roadwork.changed_by_team {
+                        ui.colored_label(Color32::from_rgb(255, 0, 255), "Changed by the team");
+                    }
+                    if StatusPanel::new(roadwork).show(ui) {
+                        local_change = true;
+                    }
+                    if AssigneePanel::new(roadwork, &author).show(ui) {
+                        local_change = true;
+                    }
+                    if NotesPanel::new(roadwork, note_draft, &author).show(ui) {
+                        local_change = true;
+                    }
                 });
             });
         }
+        if local_change {
+            if let Some(roadwork_data) = self.roadwork_data.get(&source) {
+                self.open_data_service_manager
+                    .save_sync_data(roadwork_data, &[&id]);
+            }
+            self.background_sync.local_change();
+        }
+        // the review is not shared with the team, only the roadwork is saved
+        if reviewed && let Some(roadwork_data) = self.roadwork_data.get(&source) {
+            self.open_data_service_manager.save(roadwork_data);
+        }
     }
 
-    fn get_wme_url_pattern(&self) -> String {
-        if let Some(opendataservice) = self.open_data_service_manager.get_opendata_service() {
+    /// Show the fields changed by the opendata service since the last review.
+    /// Returns true if they were marked as reviewed
+    fn show_changes(ui: &mut Ui, roadwork: &mut Roadwork) -> bool {
+        ui.separator();
+        ui.label(
+            RichText::new("Modified since last review:")
+                .strong()
+                .color(Color32::ORANGE),
+        );
+        egui::Grid::new("changes_grid")
+            .num_columns(3)
+            .spacing([4.0, 4.0])
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label(RichText::new("Field").strong());
+                ui.label(RichText::new("Old").strong());
+                ui.label(RichText::new("New").strong());
+                ui.end_row();
+                for change in &roadwork.changes {
+                    ui.label(&change.field);
+                    ui.label(Self::get_multiline_text(&change.old));
+                    ui.label(Self::get_multiline_text(&change.new));
+                    ui.end_row();
+                }
+            });
+        let reviewed = ui.button("Mark as reviewed").clicked();
+        if reviewed {
+            roadwork.changes.clear();
+        }
+        ui.separator();
+        reviewed
+    }
+
+    fn get_wme_url_pattern(&self, source: &str) -> String {
+        if let Some(opendataservice) = self.open_data_service_manager.get_service(source) {
             if let Some(editor_pattern) =
                 &opendataservice.service_descriptor.metadata.editor_pattern
             {
@@ -220,6 +724,12 @@ impl RoadworkApp {
     fn show_top_panel(&mut self, ctx: &Context) {
         egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
             egui::MenuBar::new().ui(ui, |ui| {
+                ui.menu_button("Settings", |ui| {
+                    if ui.button("Synchronization").clicked() {
+                        self.open_sync_settings_dialog();
+                        ui.close();
+                    }
+                });
                 ui.menu_button("Help", |ui| {
                     if ui.button("About").clicked() {
                         self.show_about_dialog = true;
@@ -255,22 +765,53 @@ impl RoadworkApp {
                             }
                         }
                     });
+                self.show_overlays_menu(ui);
                 if ui.button("Reload").clicked() {
                     self.reload_data();
                 }
+                self.show_loading(ui);
                 ui.checkbox(
                     &mut self.settings.lock().unwrap().hide_expired,
                     "Hide expired",
                 );
+                ui.checkbox(&mut self.settings.lock().unwrap().only_mine, "My roadworks")
+                    .on_hover_text("Only show the roadworks assigned to me");
                 LogsPanel::new(&mut self.logs_panel_open).show_button(ctx, ui);
+                let current = self.settings.lock().unwrap().opendata_service.clone();
+                DiagnosticsPanel::new(
+                    &mut self.diagnostics_panel_open,
+                    self.roadwork_data.get(&current),
+                )
+                .show_button(ctx, ui);
 
                 // Info button to show source metadata
                 if ui.button("Info").clicked() {
                     self.show_info_dialog = true;
                 }
+                if ui.button("History").clicked() {
+                    let source = self.settings.lock().unwrap().opendata_service.clone();
+                    self.history = Some(self.open_data_service_manager.load_history(&source));
+                    self.show_history_dialog = true;
+                }
+                Self::show_last_fetch(ui, self.roadwork_data.get(&current));
+                self.show_sync_state(ui);
+                if !self.conflicts.is_empty()
+                    && ui
+                        .button(
+                            RichText::new(format!("Conflicts ({})", self.conflicts.len()))
+                                .color(Color32::ORANGE),
+                        )
+                        .clicked()
+                {
+                    self.show_conflicts_dialog = true;
+                }
             });
         });
 
+        self.show_history_dialog(ctx);
+        self.show_conflicts_dialog(ctx);
+        self.show_sync_settings_dialog(ctx);
+
         // Source Info dialog
         if self.show_info_dialog {
             if let Some(ods) = self.open_data_service_manager.get_opendata_service() {
@@ -289,6 +830,76 @@ impl RoadworkApp {
         }
     }
 
+    /// Let the user choose the services shown in addition to the selected one
+    fn show_overlays_menu(&mut self, ui: &mut Ui) {
+        ui.menu_button("Overlays", |ui| {
+            let services = self.open_data_service_manager.services().to_owned();
+            let mut changed = false;
+            {
+                let mut settings = self.settings.lock().unwrap();
+                for service in services {
+                    if service == settings.opendata_service {
+                        continue;
+                    }
+                    let mut shown = settings.overlay_services.contains(&service);
+                    if ui.checkbox(&mut shown, &service).changed() {
+                        if shown {
+                            settings.overlay_services.push(service);
+                        } else {
+                            settings.overlay_services.retain(|s| *s != service);
+                        }
+                        changed = true;
+                    }
+                }
+            }
+            if changed {
+                self.start_loading(false);
+            }
+        });
+    }
+
+    /// Show when the data was fetched, and if the last refresh failed
+    fn show_last_fetch(ui: &mut Ui, roadwork_data: Option<&RoadworkData>) {
+        let Some(roadwork_data) = roadwork_data else {
+            return;
+        };
+        ui.label(format!(
+            "Updated {}",
+            format_date(roadwork_data.created.as_millis() as i64)
+        ));
+        if roadwork_data.stale {
+            ui.colored_label(
+                Color32::ORANGE,
+                format!("Stale ({} old)", format_age(roadwork_data.age())),
+            )
+            .on_hover_text("The last refresh failed, the previously fetched data is shown");
+        }
+    }
+
+    fn show_history_dialog(&mut self, ctx: &Context) {
+        if !self.show_history_dialog {
+            return;
+        }
+        let Some(history) = &self.history else {
+            return;
+        };
+        match HistoryDialog::new(&mut self.show_history_dialog, history).show(ctx) {
+            Some(HistoryAction::Export) => {
+                match self.open_data_service_manager.export_history(history) {
+                    Ok(path) => self
+                        .toasts
+                        .success(format!("History exported to {}", path.display())),
+                    Err(e) => self.toasts.error(format!("Unable to export history: {e}")),
+                };
+            }
+            Some(HistoryAction::Show(position)) => {
+                self.position = position;
+                self.map_memory.follow_my_position();
+            }
+            None => {}
+        }
+    }
+
     fn draw_zoom_level(&mut self, ui: &mut Ui, response: Response) {
         let painter = ui.painter_at(response.rect);
         let margin = egui::vec2(6.0, 6.0); // distance from map edges
@@ -324,6 +935,9 @@ impl RoadworkApp {
 
 impl App for RoadworkApp {
     fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
+        self.poll_loaders();
+        self.background_sync(ctx);
+        self.show_sync_errors();
         self.show_top_panel(ctx);
         self.show_left_panel(ctx);
 
@@ -342,25 +956,50 @@ impl App for RoadworkApp {
                 self.position = center.into();
             }
 
-            if let Some(roadwork_data) = &self.roadwork_data {
-                let projector =
-                    Projector::new(response.rect, &self.map_memory, self.position.into());
-                if response.clicked() {
-                    self.selected_roadwork = None;
-                }
-                let hide_expired = self.settings.lock().unwrap().hide_expired;
+            let projector = Projector::new(response.rect, &self.map_memory, self.position.into());
+            let mut selection = None;
+            let (hide_expired, only_mine, login, services) = {
+                let settings = self.settings.lock().unwrap();
+                (
+                    settings.hide_expired,
+                    settings.only_mine,
+                    settings.synchronization_login.clone(),
+                    settings.displayed_services(),
+                )
+            };
+            // the services are told apart by a colored ring when several are shown
+            let several_services = services.len() > 1;
+            for (index, source) in services.iter().enumerate() {
+                let Some(roadwork_data) = self.roadwork_data.get(source) else {
+                    continue;
+                };
+                let color = several_services.then(|| source_color(index));
                 for (id, marker) in roadwork_data.roadworks.iter() {
                     if hide_expired && marker.is_expired() {
                         continue;
                     }
+                    let assignment = Assignment::of(marker, &login);
+                    if only_mine && assignment != Some(Assignment::Mine) {
+                        continue;
+                    }
                     if ui
-                        .add(RoadworkMarker::new(marker, &projector, response.clicked()))
+                        .add(
+                            RoadworkMarker::new(marker, &projector, response.clicked())
+                                .with_source_color(color)
+                                .with_assignment(assignment),
+                        )
                         .changed()
                     {
-                        self.selected_roadwork = Some(id.to_owned());
+                        selection = Some((source.clone(), id.to_owned()));
                     }
                 }
             }
+            if response.clicked() || selection.is_some() {
+                self.select_roadwork(selection);
+            }
+            if several_services {
+                Legend::new(&services).show(ui, response.rect);
+            }
 
             self.draw_zoom_level(ui, response);
         });
@@ -369,7 +1008,7 @@ impl App for RoadworkApp {
 
     fn save(&mut self, _storage: &mut dyn Storage) {
         info!("Saving data");
-        if let Some(roadwork_data) = &self.roadwork_data {
+        for roadwork_data in self.roadwork_data.values() {
             self.open_data_service_manager.save(roadwork_data);
         }
         let mut settings = self.settings.lock().unwrap();
@@ -0,0 +1,101 @@
+use crate::opendata::open_data_service_manager::OpenDataServiceManager;
+use egui::Context;
+use log::info;
+use roadwork_sync::{SyncRequest, SyncResponse};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The number of seconds between two synchronizations when the settings don't define it
+pub(crate) const DEFAULT_SYNC_INTERVAL: u64 = 60;
+
+/// The delay before a local change is sent, so that several quick changes are sent together
+const PUSH_DELAY: Duration = Duration::from_secs(5);
+
+/// The requests sent by the worker with the response of the server
+type Responses = Vec<(SyncRequest, SyncResponse)>;
+
+/// Synchronize the shown services periodically on a worker thread, so the changes of the team
+/// appear without reloading, and shortly after the user changed a status
+pub(crate) struct BackgroundSync {
+    last_sync: Instant,
+    /// When the user changed a status that was not sent yet
+    local_change: Option<Instant>,
+    receiver: Option<Receiver<Responses>>,
+}
+
+impl BackgroundSync {
+    pub(crate) fn new() -> Self {
+        Self {
+            last_sync: Instant::now(),
+            local_change: None,
+            receiver: None,
+        }
+    }
+
+    /// The data was just synchronized by a loading
+    pub(crate) fn synchronized(&mut self) {
+        self.last_sync = Instant::now();
+    }
+
+    pub(crate) fn local_change(&mut self) {
+        self.local_change.get_or_insert_with(Instant::now);
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    /// Returns how long to wait before the next synchronization, zero if it is due
+    pub(crate) fn next_sync(&self, interval: Duration) -> Duration {
+        let periodic = interval.saturating_sub(self.last_sync.elapsed());
+        match self.local_change {
+            Some(local_change) => periodic.min(PUSH_DELAY.saturating_sub(local_change.elapsed())),
+            None => periodic,
+        }
+    }
+
+    /// Send the requests on a worker thread, the UI is repainted when it is done
+    pub(crate) fn start(
+        &mut self,
+        manager: Arc<OpenDataServiceManager>,
+        requests: Vec<SyncRequest>,
+        egui_ctx: Context,
+    ) {
+        info!("Start the background synchronization");
+        self.last_sync = Instant::now();
+        self.local_change = None;
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let synchronization_service = manager.synchronization_service();
+            // the failures are reported by the synchronization state
+            let responses: Responses = requests
+                .into_iter()
+                .filter_map(|request| {
+                    let response = synchronization_service.send(&request).ok()?;
+                    Some((request, response))
+                })
+                .collect();
+            sender.send(responses).ok();
+            egui_ctx.request_repaint();
+        });
+        self.receiver = Some(receiver);
+    }
+
+    /// Returns the responses once the worker is done
+    pub(crate) fn poll(&mut self) -> Option<Responses> {
+        let receiver = self.receiver.as_ref()?;
+        match receiver.try_recv() {
+            Ok(responses) => {
+                self.receiver = None;
+                Some(responses)
+            }
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.receiver = None;
+                None
+            }
+        }
+    }
+}
@@ -1,31 +1,242 @@
-use log::info;
-use roadwork_sync::SyncData;
-use serde::Deserialize;
-use serde::de::DeserializeOwned;
-use std::collections::HashMap;
+use crate::MyError;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use log::{error, info, warn};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::{Certificate, Proxy};
+use roadwork_sync::{SyncError, Transport, TransportResponse};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
 
-#[derive(Debug, Deserialize, Default)]
-pub(crate) struct HttpService;
+/// The configuration of the http client, the proxy environment variables
+/// (HTTP_PROXY, HTTPS_PROXY, NO_PROXY) are used when no proxy is defined
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct HttpSettings {
+    /// The number of seconds to wait for the connection
+    pub(crate) connect_timeout: u64,
+    /// The number of seconds to wait for the whole response
+    pub(crate) timeout: u64,
+    /// The number of times a GET or HEAD request is sent again after a server or connection error
+    pub(crate) retries: u32,
+    /// The url of the proxy used for http and https, like http://proxy:3128
+    pub(crate) proxy: Option<String>,
+    /// A pem file with the certificates of the authorities to trust in addition to the system ones
+    pub(crate) ca_bundle: Option<PathBuf>,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout: 10,
+            timeout: 120,
+            retries: 3,
+            proxy: None,
+            ca_bundle: None,
+        }
+    }
+}
+
+/// The http client shared by the opendata services, the bootstrap and the synchronization
+#[derive(Debug)]
+pub(crate) struct HttpService {
+    client: Client,
+    retries: u32,
+}
+
+impl Default for HttpService {
+    fn default() -> Self {
+        Self::new(&HttpSettings::default()).expect("Unable to create the default http client")
+    }
+}
 
 impl HttpService {
+    /// The delay before the first retry, it doubles for each retry
+    const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+    pub(crate) fn new(settings: &HttpSettings) -> Result<Self, MyError> {
+        let mut builder = Client::builder()
+            .user_agent(concat!("Roadwork-rs/", env!("CARGO_PKG_VERSION")))
+            .connect_timeout(Duration::from_secs(settings.connect_timeout))
+            .timeout(Duration::from_secs(settings.timeout));
+        if let Some(proxy) = &settings.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+        if let Some(ca_bundle) = &settings.ca_bundle {
+            for certificate in Certificate::from_pem_bundle(&std::fs::read(ca_bundle)?)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        Ok(Self {
+            client: builder.build()?,
+            retries: settings.retries,
+        })
+    }
+
+    /// Create the client, the default configuration is used if the settings are invalid
+    pub(crate) fn from_settings(settings: &HttpSettings) -> Self {
+        Self::new(settings).unwrap_or_else(|e| {
+            error!("Invalid http settings, using the default ones: {e}");
+            Self::default()
+        })
+    }
+
     pub(crate) fn get_url(&self, url: &str) -> reqwest::Result<String> {
         info!("get_url {url}");
-        reqwest::blocking::get(url)?.text()
+        self.execute(self.client.get(url), self.retries)?
+            .error_for_status()?
+            .text()
     }
 
-    pub(crate) fn post_json_object<T: DeserializeOwned>(
+    /// Send a request with custom method, headers and body, the returned response can be read as a stream
+    pub(crate) fn send(
         &self,
+        method: reqwest::Method,
         url: &str,
-        body: &HashMap<String, SyncData>,
-        headers: &HashMap<String, String>,
-    ) -> reqwest::Result<T> {
-        info!("post_json_object");
-        let client = reqwest::blocking::Client::new();
-        let mut request_builder = client.request(reqwest::Method::POST, url);
-        for header in headers {
-            request_builder = request_builder.header(header.0, header.1);
+        headers: &[(String, String)],
+        body: Option<String>,
+    ) -> reqwest::Result<Response> {
+        info!("send {method} {url}");
+        let retries = self.retries_of(&method);
+        let mut request_builder = self.client.request(method, url);
+        for (name, value) in headers {
+            request_builder = request_builder.header(name, value);
+        }
+        if let Some(body) = body {
+            request_builder = request_builder.body(body);
+        }
+        self.execute(request_builder, retries)?.error_for_status()
+    }
+
+    /// Only the idempotent requests are retried, the server may have applied
+    /// a request that timed out and sending it again would apply it twice
+    fn retries_of(&self, method: &reqwest::Method) -> u32 {
+        if *method == reqwest::Method::GET || *method == reqwest::Method::HEAD {
+            self.retries
+        } else {
+            0
         }
+    }
+
+    /// Send the request, it is sent again with an exponential delay when the server
+    /// answers with a 5xx status or when the connection fails
+    fn execute(&self, request_builder: RequestBuilder, retries: u32) -> reqwest::Result<Response> {
+        let mut attempt = 0;
+        loop {
+            let retry = request_builder.try_clone().filter(|_| attempt < retries);
+            let result = match retry {
+                Some(request_builder) => request_builder.send(),
+                None => return request_builder.send(),
+            };
+            match &result {
+                Ok(response) if response.status().is_server_error() => {
+                    warn!("{} answered {}", response.url(), response.status());
+                }
+                Err(e) if e.is_connect() || e.is_timeout() => warn!("Request failed: {e}"),
+                _ => return result,
+            }
+            let delay = Self::RETRY_DELAY * 2u32.pow(attempt);
+            info!("Retrying in {delay:?}");
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+}
+
+/// The synchronization requests go through the shared client to use its settings,
+/// they are not retried since they are not idempotent
+impl Transport for HttpService {
+    fn post(
+        &self,
+        url: &str,
+        headers: &[(String, String)],
+        body: Vec<u8>,
+    ) -> Result<TransportResponse, SyncError> {
+        info!("post {url}");
+        let mut request_builder = self.client.post(url).body(body);
+        for (name, value) in headers {
+            request_builder = request_builder.header(name, value);
+        }
+        let response = self
+            .execute(request_builder, self.retries_of(&reqwest::Method::POST))
+            .map_err(|e| SyncError::Transport(e.to_string()))?;
+        let status = response.status().as_u16();
+        let body = response
+            .bytes()
+            .map_err(|e| SyncError::Transport(e.to_string()))?;
+        Ok(TransportResponse {
+            status,
+            body: body.to_vec(),
+        })
+    }
+}
+
+/// Build the value of a Basic Authorization header
+pub(crate) fn basic_auth(login: &str, password: &str) -> String {
+    let encoded_auth = BASE64_STANDARD.encode(format!("{login}:{password}"));
+    format!("Basic {encoded_auth}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Answer each connection with the next status, the body is the status
+    fn serve(statuses: Vec<u16>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for (status, stream) in statuses.into_iter().zip(listener.incoming()) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).is_ok() && line != "\r\n" {
+                    line.clear();
+                }
+                let body = status.to_string();
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} Status\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+        format!("http://{address}/")
+    }
+
+    #[test]
+    fn test_retry_server_error() -> Result<(), MyError> {
+        let url = serve(vec![503, 200]);
+        let http_service = HttpService::new(&HttpSettings {
+            retries: 1,
+            ..HttpSettings::default()
+        })?;
+        assert_eq!(http_service.get_url(&url)?, "200");
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_retry_client_error() -> Result<(), MyError> {
+        let url = serve(vec![404, 200]);
+        let http_service = HttpService::new(&HttpSettings::default())?;
+        assert!(http_service.get_url(&url).is_err());
+        Ok(())
+    }
 
-        request_builder.json(body).send()?.json()
+    #[test]
+    fn test_no_retry_post() -> Result<(), MyError> {
+        let url = serve(vec![503, 200]);
+        let http_service = HttpService::new(&HttpSettings {
+            retries: 1,
+            ..HttpSettings::default()
+        })?;
+        let response = Transport::post(&http_service, &url, &[], Vec::new())?;
+        assert_eq!(response.status, 503);
+        Ok(())
     }
 }
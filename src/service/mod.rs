@@ -1,2 +1,3 @@
+pub(crate) mod background_sync;
 pub(crate) mod http_service;
 pub(crate) mod synchronization_service;
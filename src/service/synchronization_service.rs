@@ -1,80 +1,654 @@
+use crate::MyError;
+use crate::credentials::{Credentials, CredentialsStore};
 use crate::model::roadwork_data::RoadworkData;
 use crate::service::http_service::HttpService;
 use crate::settings::Settings;
-use base64::Engine;
-use base64::prelude::BASE64_STANDARD;
-use log::{info, warn};
-use roadwork_sync::SyncData;
-use std::collections::HashMap;
+use crate::store::json_store::JsonStore;
+use log::{error, info, warn};
+use roadwork_sync::{Conflict, SyncClient, SyncConfig, SyncError, SyncRequest, SyncResponse};
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The result of the last synchronization, shown in the UI
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) enum SyncState {
+    #[default]
+    Disabled,
+    Synchronized(Duration),
+    Failed {
+        time: Duration,
+        error: String,
+    },
+}
 
 pub(crate) struct SynchronizationService {
     settings: Arc<Mutex<Settings>>,
-    http_service: HttpService,
+    http_service: Arc<HttpService>,
+    state: Mutex<SyncState>,
+    /// The errors not shown to the user yet
+    errors: Mutex<Vec<String>>,
+    /// The local changes not sent yet, at most one request by service
+    queue: Mutex<Vec<SyncRequest>>,
+    /// The responses to the queued requests, not applied to the data yet
+    received: Mutex<Vec<(SyncRequest, SyncResponse)>>,
+    /// The conflicts left to the user, with the service of their roadwork
+    conflicts: Mutex<Vec<(String, Conflict)>>,
+    /// Where the queue is kept between two launches
+    queue_path: Option<PathBuf>,
+    credentials: Mutex<Credentials>,
+    credentials_store: Option<CredentialsStore>,
+    /// True once the server answered that it does not give tokens
+    token_unsupported: AtomicBool,
+    /// True when the data is read from fixtures, it must not be sent to the team
+    offline: bool,
     // localizationService: LocalizationService,
 }
 
 impl SynchronizationService {
-    pub(crate) fn new(settings: Arc<Mutex<Settings>>) -> SynchronizationService {
-        Self {
+    /// Create the service, the queue and the credentials are kept in the folder
+    pub(crate) fn new(
+        settings: Arc<Mutex<Settings>>,
+        http_service: Arc<HttpService>,
+        folder: Option<PathBuf>,
+    ) -> SynchronizationService {
+        let queue_path = folder.as_ref().map(|folder| folder.join("sync_queue.json"));
+        let service = Self {
             settings,
-            http_service: HttpService,
+            http_service,
+            state: Mutex::new(SyncState::Disabled),
+            errors: Mutex::new(Vec::new()),
+            queue: Mutex::new(Self::load_queue(queue_path.as_ref())),
+            received: Mutex::new(Vec::new()),
+            conflicts: Mutex::new(Vec::new()),
+            queue_path,
+            credentials: Mutex::new(Credentials::default()),
+            credentials_store: folder.as_deref().map(CredentialsStore::new),
+            token_unsupported: AtomicBool::new(false),
+            offline: false,
+        };
+        service.load_credentials();
+        service
+    }
+
+    pub(crate) fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Read the credentials file, the password found in the settings written by the previous
+    /// versions is moved to it
+    fn load_credentials(&self) {
+        let Some(credentials_store) = &self.credentials_store else {
+            return;
+        };
+        let passphrase = CredentialsStore::passphrase();
+        let mut credentials = match credentials_store.load(passphrase.as_deref()) {
+            Ok(credentials) => credentials,
+            Err(e) => {
+                error!("Unable to read the credentials: {e}");
+                self.errors.lock().unwrap().push(e.to_string());
+                return;
+            }
+        };
+        let legacy_password =
+            std::mem::take(&mut self.settings.lock().unwrap().synchronization_password);
+        if !legacy_password.is_empty() && credentials.password.is_empty() {
+            info!("Move the synchronization password to the credentials file");
+            credentials.password = legacy_password;
+            self.save_credentials(&credentials);
+        }
+        *self.credentials.lock().unwrap() = credentials;
+    }
+
+    fn save_credentials(&self, credentials: &Credentials) {
+        if let Some(credentials_store) = &self.credentials_store
+            && let Err(e) =
+                credentials_store.save(credentials, CredentialsStore::passphrase().as_deref())
+        {
+            error!("Unable to save the credentials: {e}");
+        }
+    }
+
+    pub(crate) fn password(&self) -> String {
+        self.credentials.lock().unwrap().password.clone()
+    }
+
+    /// Change the password, a new token is asked at the next synchronization
+    /// as the server, the team or the login may have changed too
+    pub(crate) fn set_password(&self, password: String) {
+        let mut credentials = self.credentials.lock().unwrap();
+        credentials.password = password;
+        credentials.token = None;
+        self.token_unsupported.store(false, Ordering::Relaxed);
+        self.save_credentials(&credentials);
+    }
+
+    fn set_token(&self, token: Option<String>) {
+        let mut credentials = self.credentials.lock().unwrap();
+        credentials.token = token;
+        self.save_credentials(&credentials);
+    }
+
+    fn load_queue(queue_path: Option<&PathBuf>) -> Vec<SyncRequest> {
+        queue_path
+            .and_then(|path| File::open(path).ok())
+            .and_then(|file| {
+                serde_json::from_reader(file)
+                    .inspect_err(|e| error!("Unable to read the synchronization queue: {e}"))
+                    .ok()
+            })
+            .unwrap_or_default()
+    }
+
+    fn save_queue(&self, queue: &[SyncRequest]) {
+        if let Some(queue_path) = &self.queue_path
+            && let Err(e) = JsonStore::write_json(queue_path, &queue)
+        {
+            error!("Unable to save the synchronization queue: {e}");
         }
     }
 }
 
 impl SynchronizationService {
+    pub(crate) fn state(&self) -> SyncState {
+        self.state.lock().unwrap().clone()
+    }
+
+    /// Returns the number of local changes waiting to be sent
+    pub(crate) fn queued_count(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    /// Returns the errors that happened since the last call
+    pub(crate) fn take_errors(&self) -> Vec<String> {
+        std::mem::take(&mut self.errors.lock().unwrap())
+    }
+
+    /// Returns the responses to the queued requests sent since the last call
+    pub(crate) fn take_received(&self) -> Vec<(SyncRequest, SyncResponse)> {
+        std::mem::take(&mut self.received.lock().unwrap())
+    }
+
+    /// Returns the conflicts found since the last call
+    pub(crate) fn take_conflicts(&self) -> Vec<(String, Conflict)> {
+        std::mem::take(&mut self.conflicts.lock().unwrap())
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.offline && self.settings.lock().unwrap().synchronization_enabled
+    }
+
     /**
-     * Synchronize the data with the server
+     * Synchronize the data with the server.
+     * Only the roadworks changed locally are sent, and the server answers with the roadworks
+     * changed since the last synchronization.
+     * The conflicts with the changes of the team are resolved according to the settings policy.
+     * The changes that could not be sent before are sent first, if the server can't be reached
+     * the local changes are queued to be sent later
      *
      * @param roadwork_data the data to synchronize. Status might be updated
      */
-    pub(crate) fn synchronize(&self, roadwork_data: &mut RoadworkData) {
-        if self.settings.lock().unwrap().synchronization_enabled {
-            info!("synchronize");
-            let url = self.get_url(&roadwork_data.source);
-            info!("Will synchronize with url {}", url);
-            let mut body: HashMap<String, SyncData> = HashMap::new();
-            roadwork_data.iter().for_each(|roadwork| {
-                body.insert(roadwork.id.clone(), roadwork.sync_data.clone());
-            });
-            let headers = self.create_headers();
-            let synchronized_data: HashMap<String, SyncData> = self
-                .http_service
-                .post_json_object(&url, &body, &headers)
-                .unwrap();
-
-            for (id, server_sync_data) in synchronized_data {
-                match roadwork_data.get_mut_roadwork(&id) {
-                    Some(roadwork) => roadwork.sync_data.copy(&server_sync_data),
-                    None => warn!("Roadwork {id} not found"),
+    pub(crate) fn synchronize(&self, roadwork_data: &mut RoadworkData) -> Result<(), MyError> {
+        if !self.is_enabled() {
+            *self.state.lock().unwrap() = SyncState::Disabled;
+            return Ok(());
+        }
+        info!("synchronize");
+        let request = roadwork_data.sync_request();
+        let result = self.send(&request);
+        // the queued changes of the service may have been sent even if this request failed
+        let (received, others): (Vec<_>, Vec<_>) = self
+            .take_received()
+            .into_iter()
+            .partition(|(queued, _)| queued.source == roadwork_data.source);
+        self.received.lock().unwrap().extend(others);
+        for (queued, response) in received {
+            self.apply(roadwork_data, &queued, response);
+        }
+        self.apply(roadwork_data, &request, result?);
+        Ok(())
+    }
+
+    /// Send the request after the queued ones, it is queued if the server can't be reached.
+    /// Returns the sync data of the roadworks changed since the request token
+    pub(crate) fn send(&self, request: &SyncRequest) -> Result<SyncResponse, MyError> {
+        info!(
+            "{} changes to send to {} since {}",
+            request.sync_data.len(),
+            request.source,
+            request.since
+        );
+        let result = self.flush_queue().and_then(|_| self.post(request));
+        if result.is_err() && !request.sync_data.is_empty() {
+            self.enqueue(request.clone());
+        }
+        self.set_state(result.as_ref().map(|_| ()));
+        result
+    }
+
+    /// Take the server response in the data, the conflicts are resolved according to the settings
+    /// policy. The roadworks whose status was changed by the team are flagged.
+    /// The roadworks changed locally after the request was made are kept to be sent again
+    pub(crate) fn apply(
+        &self,
+        roadwork_data: &mut RoadworkData,
+        request: &SyncRequest,
+        response: SyncResponse,
+    ) {
+        let policy = self.settings.lock().unwrap().conflict_policy;
+        let mut conflicts = Vec::new();
+        for (id, server_sync_data) in response {
+            let Some(roadwork) = roadwork_data.roadworks.get_mut(&id) else {
+                warn!("Roadwork {id} not found");
+                continue;
+            };
+            let sent_time = request
+                .sync_data
+                .get(&id)
+                .map(|sent| sent.local_update_time());
+            if sent_time.is_some_and(|sent_time| roadwork.sync_data.local_update_time() > sent_time)
+            {
+                roadwork.sync_data.keep_local(&server_sync_data);
+                continue;
+            }
+            let previous_status = roadwork.sync_data.status;
+            let previous_assignee = roadwork.sync_data.assignee().map(str::to_string);
+            let previous_notes = roadwork.sync_data.notes().len();
+            if roadwork.sync_data.resolve(&server_sync_data, policy) {
+                info!("Conflict on roadwork {id}");
+                conflicts.push((
+                    roadwork_data.source.clone(),
+                    Conflict {
+                        id,
+                        local: roadwork.sync_data.clone(),
+                        server: server_sync_data,
+                    },
+                ));
+            } else if sent_time.is_none()
+                && (roadwork.sync_data.status != previous_status
+                    || roadwork.sync_data.assignee() != previous_assignee.as_deref()
+                    || roadwork.sync_data.notes().len() != previous_notes)
+            {
+                roadwork.changed_by_team = true;
+            }
+        }
+        self.conflicts.lock().unwrap().extend(conflicts);
+    }
+
+    /// Send the queued changes, their responses are kept to be applied to the data
+    pub(crate) fn retry(&self) -> Result<(), MyError> {
+        let result = self.flush_queue();
+        self.set_state(result.as_ref().map(|_| ()));
+        result
+    }
+
+    /// The queue is taken out while it is sent so that the UI is not blocked by the requests,
+    /// the requests that could not be sent are put back
+    fn flush_queue(&self) -> Result<(), MyError> {
+        let mut queue = std::mem::take(&mut *self.queue.lock().unwrap());
+        if queue.is_empty() {
+            return Ok(());
+        }
+        info!("Sending {} queued synchronizations", queue.len());
+        let mut result = Ok(());
+        while !queue.is_empty() {
+            match self.post(&queue[0]) {
+                Ok(response) => {
+                    let queued = queue.remove(0);
+                    self.received.lock().unwrap().push((queued, response));
+                }
+                Err(e) => {
+                    result = Err(e);
+                    break;
                 }
             }
         }
+        let mut pending = self.queue.lock().unwrap();
+        for request in std::mem::take(&mut *pending) {
+            Self::coalesce(&mut queue, request);
+        }
+        *pending = queue;
+        self.save_queue(&pending);
+        result
     }
 
-    fn get_url(&self, source: &str) -> String {
+    fn enqueue(&self, request: SyncRequest) {
+        let mut queue = self.queue.lock().unwrap();
+        Self::coalesce(&mut queue, request);
+        self.save_queue(&queue);
+    }
+
+    /// Add the request to the queue, merged with the queued request of its service
+    /// so that only the latest change of each roadwork is kept
+    fn coalesce(queue: &mut Vec<SyncRequest>, request: SyncRequest) {
+        let Some(queued) = queue.iter_mut().find(|q| q.source == request.source) else {
+            queue.push(request);
+            return;
+        };
+        queued.since = queued.since.min(request.since);
+        for (id, sync_data) in request.sync_data {
+            match queued.sync_data.get(&id) {
+                Some(previous) if previous.local_update_time() > sync_data.local_update_time() => {}
+                _ => {
+                    queued.sync_data.insert(id, sync_data);
+                }
+            }
+        }
+    }
+
+    fn set_state(&self, result: Result<(), &MyError>) {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let state = match result {
+            Ok(()) => SyncState::Synchronized(time),
+            Err(e) => {
+                error!("Synchronization failed: {e}");
+                let error = e.to_string();
+                self.errors
+                    .lock()
+                    .unwrap()
+                    .push(format!("Synchronization failed: {error}"));
+                SyncState::Failed { time, error }
+            }
+        };
+        *self.state.lock().unwrap() = state;
+    }
+
+    /// The client is made for each request as the settings may change
+    fn client(&self) -> SyncClient<&HttpService> {
         let settings = self.settings.lock().unwrap();
-        let synchronization_team = &settings.synchronization_team;
-        let mut url = settings.synchronization_url.clone();
-        if !url.ends_with("/") {
-            url.push('/');
+        let credentials = self.credentials.lock().unwrap();
+        let config = SyncConfig {
+            url: settings.synchronization_url.clone(),
+            team: settings.synchronization_team.clone(),
+            login: settings.synchronization_login.clone(),
+            password: credentials.password.clone(),
+            token: credentials.token.clone(),
+        };
+        SyncClient::new(config, &self.http_service)
+    }
+
+    fn post(&self, request: &SyncRequest) -> Result<SyncResponse, MyError> {
+        let has_token = self.ensure_token()?;
+        match self.client().set_data(request) {
+            Err(SyncError::Unauthorized) if has_token => {
+                info!("The token was refused, asking a new one");
+                self.set_token(None);
+                self.ensure_token()?;
+                Ok(self.client().set_data(request)?)
+            }
+            result => Ok(result?),
+        }
+    }
+
+    /// Check that the server accepts the configuration with a request that sends and receives nothing
+    pub(crate) fn test_connection(
+        &self,
+        mut config: SyncConfig,
+        source: &str,
+    ) -> Result<(), MyError> {
+        match SyncClient::new(config.clone(), self.http_service.as_ref()).login() {
+            Ok(token) => config.token = Some(token),
+            Err(SyncError::Status { status: 404, .. }) => {}
+            Err(e) => return Err(e.into()),
         }
-        format!("{url}/setData/{synchronization_team}/{source}")
+        SyncClient::new(config, self.http_service.as_ref())
+            .set_data(&SyncRequest::new(source, u64::MAX))?;
+        Ok(())
     }
 
-    fn create_headers(&self) -> HashMap<String, String> {
-        let auth = {
-            let settings = self.settings.lock().unwrap();
-            format!(
-                "{}:{}",
-                settings.synchronization_login, settings.synchronization_password
-            )
+    /// Exchange the login and password for a token if there is none yet.
+    /// Returns false if the server does not give tokens, the login and password are then sent
+    fn ensure_token(&self) -> Result<bool, MyError> {
+        if self.credentials.lock().unwrap().token.is_some() {
+            return Ok(true);
+        }
+        if self.token_unsupported.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+        match self.client().login() {
+            Ok(token) => {
+                self.set_token(Some(token));
+                Ok(true)
+            }
+            Err(SyncError::Status { status: 404, .. }) => {
+                info!("The server does not give tokens");
+                self.token_unsupported.store(true, Ordering::Relaxed);
+                Ok(false)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::roadwork::Roadwork;
+    use crate::service::http_service::HttpSettings;
+    use roadwork_server::SyncServer;
+    use roadwork_sync::{Status, SyncData};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    fn service(folder: &Path) -> SynchronizationService {
+        // nothing listens on the discard port
+        service_of("http://127.0.0.1:9", "login", folder.to_path_buf())
+    }
+
+    fn service_of(url: &str, login: &str, folder: PathBuf) -> SynchronizationService {
+        let settings: Settings = serde_json::from_value(json!({
+            "opendataService": "France-Paris",
+            "synchronizationUrl": url,
+            "synchronizationTeam": "team",
+            "synchronizationEnabled": true,
+            "synchronizationLogin": login,
+            "synchronizationPassword": "password",
+            "hide_expired": false
+        }))
+        .unwrap();
+        let http_service = HttpService::new(&HttpSettings {
+            retries: 0,
+            ..HttpSettings::default()
+        })
+        .unwrap();
+        SynchronizationService::new(
+            Arc::new(Mutex::new(settings)),
+            Arc::new(http_service),
+            Some(folder),
+        )
+    }
+
+    fn roadwork_data() -> RoadworkData {
+        let roadworks = ["1", "2", "3"]
+            .iter()
+            .map(|id| Roadwork {
+                id: id.to_string(),
+                ..Roadwork::default()
+            })
+            .collect();
+        RoadworkData::new("France-Paris", roadworks)
+    }
+
+    /// The sync data as the server answers it, updated at the given time
+    fn updated(sync_data: &SyncData, time: u64) -> SyncData {
+        let mut server_sync_data = serde_json::to_value(sync_data).unwrap();
+        server_sync_data["localUpdateTime"] = json!(time);
+        server_sync_data["serverUpdateTime"] = json!(time);
+        serde_json::from_value(server_sync_data).unwrap()
+    }
+
+    #[test]
+    fn test_only_changes_are_sent() -> Result<(), MyError> {
+        let folder = tempfile::tempdir()?;
+        let service = service(folder.path());
+        let mut roadwork_data = roadwork_data();
+        assert!(roadwork_data.sync_request().sync_data.is_empty());
+        let roadwork = roadwork_data.get_mut_roadwork("2").unwrap();
+        roadwork.sync_data.set_status(Status::Treated, 1000);
+        let request = roadwork_data.sync_request();
+        assert_eq!(request.sync_data.len(), 1);
+        assert_eq!(request.sync_data["2"].status, Status::Treated);
+
+        // the server accepted the change and another member of the team ignored the roadwork 3
+        let accepted = updated(&request.sync_data["2"], 2000);
+        let mut ignored = SyncData::default();
+        ignored.set_status(Status::Ignored, 1500);
+        let ignored = updated(&ignored, 1500);
+        let response = HashMap::from([("2".to_string(), accepted), ("3".to_string(), ignored)]);
+        service.apply(&mut roadwork_data, &request, response);
+        assert!(roadwork_data.sync_request().sync_data.is_empty());
+        assert_eq!(roadwork_data.sync_request().since, 2000);
+        assert!(!roadwork_data.roadworks["2"].changed_by_team);
+        assert!(roadwork_data.roadworks["3"].changed_by_team);
+        assert_eq!(
+            roadwork_data.roadworks["3"].sync_data.status,
+            Status::Ignored
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_during_request_is_kept() -> Result<(), MyError> {
+        let folder = tempfile::tempdir()?;
+        let service = service(folder.path());
+        let mut roadwork_data = roadwork_data();
+        let roadwork = roadwork_data.get_mut_roadwork("1").unwrap();
+        roadwork.sync_data.set_status(Status::Treated, 1000);
+        let request = roadwork_data.sync_request();
+        let roadwork = roadwork_data.get_mut_roadwork("1").unwrap();
+        roadwork.sync_data.set_status(Status::Later, 1100);
+
+        let accepted = updated(&request.sync_data["1"], 2000);
+        service.apply(
+            &mut roadwork_data,
+            &request,
+            HashMap::from([("1".to_string(), accepted)]),
+        );
+        let request = roadwork_data.sync_request();
+        assert_eq!(request.sync_data["1"].status, Status::Later);
+        assert_eq!(request.sync_data["1"].server_update_time(), 2000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreachable_server_is_queued() -> Result<(), MyError> {
+        let folder = tempfile::tempdir()?;
+        let queue_path = folder.path().join("sync_queue.json");
+        let service = service(folder.path());
+        let mut roadwork_data = roadwork_data();
+        let roadwork = roadwork_data.get_mut_roadwork("1").unwrap();
+        roadwork.sync_data.set_status(Status::Later, 1000);
+        assert!(service.synchronize(&mut roadwork_data).is_err());
+        assert!(matches!(service.state(), SyncState::Failed { .. }));
+        assert_eq!(service.take_errors().len(), 1);
+        assert_eq!(service.queued_count(), 1);
+
+        // the queue keeps only the latest change of each roadwork until the server is reachable
+        assert!(service.synchronize(&mut roadwork_data).is_err());
+        assert_eq!(service.queued_count(), 1);
+        let roadwork = roadwork_data.get_mut_roadwork("1").unwrap();
+        roadwork.sync_data.set_status(Status::Treated, 2000);
+        let roadwork = roadwork_data.get_mut_roadwork("2").unwrap();
+        roadwork.sync_data.set_status(Status::Ignored, 2000);
+        assert!(service.synchronize(&mut roadwork_data).is_err());
+        assert!(service.retry().is_err());
+        let queue = SynchronizationService::load_queue(Some(&queue_path));
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].sync_data.len(), 2);
+        assert_eq!(queue[0].sync_data["1"].status, Status::Treated);
+        Ok(())
+    }
+
+    #[test]
+    fn test_offline_is_not_synchronized() -> Result<(), MyError> {
+        let folder = tempfile::tempdir()?;
+        let service = service(folder.path()).with_offline(true);
+        let mut roadwork_data = roadwork_data();
+        let roadwork = roadwork_data.get_mut_roadwork("1").unwrap();
+        roadwork.sync_data.set_status(Status::Finished, 1000);
+        service.synchronize(&mut roadwork_data)?;
+        assert_eq!(service.state(), SyncState::Disabled);
+        assert_eq!(service.queued_count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_synchronize_with_server() -> Result<(), MyError> {
+        let folder = tempfile::tempdir()?;
+        let server_folder = folder.path().join("server");
+        std::fs::create_dir(&server_folder)?;
+        std::fs::write(
+            server_folder.join("users.json"),
+            r#"{"team": {"alice": "password", "bob": "password"}}"#,
+        )?;
+        let server = Arc::new(
+            SyncServer::new("127.0.0.1:0", server_folder)
+                .map_err(|e| MyError::ParsingError(e.to_string()))?,
+        );
+        let url = format!("http://{}", server.local_addr().unwrap());
+        let running = Arc::clone(&server);
+        std::thread::spawn(move || running.run());
+
+        let alice = service_of(&url, "alice", folder.path().join("alice"));
+        let bob = service_of(&url, "bob", folder.path().join("bob"));
+        let mut alice_data = roadwork_data();
+        let mut bob_data = roadwork_data();
+        let roadwork = alice_data.get_mut_roadwork("1").unwrap();
+        roadwork.sync_data.set_status(Status::Treated, 1000);
+        alice.synchronize(&mut alice_data)?;
+        assert!(!alice_data.roadworks["1"].sync_data.is_dirty());
+
+        bob.synchronize(&mut bob_data)?;
+        assert_eq!(bob_data.roadworks["1"].sync_data.status, Status::Treated);
+        assert!(bob_data.roadworks["1"].changed_by_team);
+        assert!(matches!(bob.state(), SyncState::Synchronized(_)));
+
+        // the password of the settings was moved to the credentials with the token
+        let settings = serde_json::to_value(&*bob.settings.lock().unwrap())?;
+        assert!(settings.get("synchronizationPassword").is_none());
+        let credentials = CredentialsStore::new(&folder.path().join("bob")).load(None)?;
+        assert_eq!(credentials.password, "password");
+        assert!(credentials.token.is_some());
+
+        let config = SyncConfig {
+            url,
+            team: "team".to_string(),
+            login: "bob".to_string(),
+            password: "password".to_string(),
+            token: None,
+        };
+        // the changes queued while offline are sent with the retry and their response is kept
+        let offline_data = &mut roadwork_data();
+        let roadwork = offline_data.get_mut_roadwork("2").unwrap();
+        roadwork.sync_data.set_status(Status::Later, 3000);
+        alice.settings.lock().unwrap().synchronization_url = "http://127.0.0.1:9".to_string();
+        assert!(alice.synchronize(offline_data).is_err());
+        alice.settings.lock().unwrap().synchronization_url = config.url.clone();
+        alice.retry()?;
+        assert_eq!(alice.queued_count(), 0);
+        for (request, response) in alice.take_received() {
+            alice.apply(offline_data, &request, response);
+        }
+        assert!(!offline_data.roadworks["2"].sync_data.is_dirty());
+        assert_eq!(
+            offline_data.roadworks["1"].sync_data.status,
+            Status::Treated
+        );
+
+        bob.test_connection(config.clone(), "France-Paris")?;
+        let wrong_password = SyncConfig {
+            password: "wrong".to_string(),
+            ..config
         };
-        let encoded_auth = BASE64_STANDARD.encode(&auth);
-        let auth_header = format!("Basic {encoded_auth}");
-        let mut headers = HashMap::new();
-        headers.insert("Authorization".to_string(), auth_header);
-        headers
+        assert!(matches!(
+            bob.test_connection(wrong_password, "France-Paris"),
+            Err(MyError::SyncError(SyncError::Unauthorized))
+        ));
+        server.stop();
+        Ok(())
     }
 }
@@ -1,6 +1,10 @@
 use crate::opendata::json::model::lat_lng::LatLng;
+use crate::service::http_service::HttpSettings;
+use crate::store::Storage;
 use log::info;
+use roadwork_sync::ConflictPolicy;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs::File;
 use std::path::PathBuf;
 use std::string::ToString;
@@ -19,17 +23,50 @@ pub struct Settings {
     pub(crate) synchronization_enabled: bool,
     #[serde(rename = "synchronizationLogin")]
     pub(crate) synchronization_login: String,
-    #[serde(rename = "synchronizationPassword")]
+    /// Only read from the settings of the previous versions, the password is moved to the
+    /// credentials file and never written here
+    #[serde(rename = "synchronizationPassword", default, skip_serializing)]
     pub(crate) synchronization_password: String,
+    /// What to do when a status was changed both locally and by another member of the team
+    #[serde(rename = "conflictPolicy", default)]
+    pub(crate) conflict_policy: ConflictPolicy,
+    /// The number of seconds between two background synchronizations
+    #[serde(rename = "syncInterval", default)]
+    pub(crate) sync_interval: Option<u64>,
+
+    /// The services shown on the map in addition to the opendata service
+    #[serde(rename = "overlayServices", default)]
+    pub(crate) overlay_services: Vec<String>,
 
     #[serde(rename = "hide_expired")]
     pub(crate) hide_expired: bool,
 
+    /// Only the roadworks assigned to the synchronization login are shown
+    #[serde(rename = "onlyMine", default)]
+    pub(crate) only_mine: bool,
+
     #[serde(rename = "mapCenter", default)]
     pub(crate) map_center: Option<LatLng>,
 
     #[serde(rename = "mapZoom", default)]
     pub(crate) map_zoom: Option<f64>,
+
+    /// Secrets used by the opendata requests (api keys, passwords...), by service name
+    #[serde(rename = "sourceSecrets", default)]
+    pub(crate) source_secrets: HashMap<String, HashMap<String, String>>,
+
+    /// The number of seconds the data is kept before being fetched again, by service name.
+    /// It overrides the refresh interval of the service descriptor
+    #[serde(rename = "refreshIntervals", default)]
+    pub(crate) refresh_intervals: HashMap<String, u64>,
+
+    /// The timeouts, retries, proxy and certificates of the http client
+    #[serde(default)]
+    pub(crate) http: HttpSettings,
+
+    /// Where the roadworks are kept between two launches
+    #[serde(default)]
+    pub(crate) storage: Storage,
 }
 
 // todo: load & save
@@ -45,14 +82,41 @@ impl Default for Settings {
                 synchronization_enabled: false,
                 synchronization_login: "".to_string(),
                 synchronization_password: "".to_string(),
+                conflict_policy: ConflictPolicy::default(),
+                sync_interval: None,
+                overlay_services: Vec::new(),
                 hide_expired: false,
+                only_mine: false,
                 map_center: None,
                 map_zoom: None,
+                source_secrets: HashMap::new(),
+                refresh_intervals: HashMap::new(),
+                http: HttpSettings::default(),
+                storage: Storage::default(),
             })
     }
 }
 
 impl Settings {
+    /// Returns the secrets defined for the given opendata service
+    pub(crate) fn secrets(&self, opendata_service: &str) -> HashMap<String, String> {
+        self.source_secrets
+            .get(opendata_service)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Returns the services shown on the map, the opendata service first
+    pub(crate) fn displayed_services(&self) -> Vec<String> {
+        let mut services = vec![self.opendata_service.clone()];
+        for service in &self.overlay_services {
+            if !services.contains(service) {
+                services.push(service.clone());
+            }
+        }
+        services
+    }
+
     pub(crate) fn save(&self) -> Result<(), std::io::Error> {
         info!("save");
         if let Some(settings_folder) = Self::settings_folder() {
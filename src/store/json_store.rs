@@ -0,0 +1,337 @@
+use crate::MyError;
+use crate::model::roadwork_data::RoadworkData;
+use crate::model::roadwork_history::RoadworkHistory;
+use crate::store::RoadworkStore;
+use crate::store::migration;
+use log::{error, info, warn};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// Store the roadworks of each service in its own json file.
+/// The files are written atomically and the previous versions are kept as backups,
+/// they are used when a file can't be read anymore
+pub(crate) struct JsonStore {
+    folder: PathBuf,
+    warnings: Mutex<Vec<String>>,
+}
+
+impl JsonStore {
+    /// The number of backups kept for each file
+    const BACKUPS: usize = 3;
+    /// The minimum age of the last backup before a new one is made,
+    /// so that the backups are not all copies of the last minutes
+    const BACKUP_INTERVAL: Duration = Duration::from_secs(3600);
+
+    pub(crate) fn new(folder: PathBuf) -> Self {
+        Self {
+            folder,
+            warnings: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn get_path(&self, source: &str) -> PathBuf {
+        self.folder.join(format!("{source}.json"))
+    }
+
+    /// The caches written before the version header were named after their version
+    fn get_legacy_path(&self, source: &str) -> PathBuf {
+        self.folder.join(format!("{source}.2.json"))
+    }
+
+    pub(crate) fn get_history_path(&self, source: &str) -> PathBuf {
+        self.folder.join(format!("{source}.history.json"))
+    }
+
+    /// Returns the path of the backup, 1 being the most recent one
+    fn backup_path(path: &Path, index: usize) -> PathBuf {
+        Self::with_suffix(path, &format!("bak.{index}"))
+    }
+
+    fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".");
+        name.push(suffix);
+        PathBuf::from(name)
+    }
+
+    fn warn(&self, message: String) {
+        warn!("{message}");
+        self.warnings.lock().unwrap().push(message);
+    }
+
+    /// Read the json file, None if it does not exist and has no backup.
+    /// If the file can't be read or is missing, the most recent valid backup is written back
+    /// in its place and returned, the unreadable file is kept aside so it is not overwritten.
+    /// A file written by a newer version is not corrupted, it is left in place and an error is returned
+    fn read_json<T>(
+        &self,
+        path: &Path,
+        parse: impl Fn(Value) -> Result<T, MyError>,
+    ) -> Result<Option<T>, MyError> {
+        let error = match Self::read_file(path, &parse) {
+            Ok(Some(value)) => return Ok(Some(value)),
+            Ok(None) if !self.has_backup(path) => return Ok(None),
+            Ok(None) => None,
+            Err(e @ MyError::UnsupportedVersion(..)) => {
+                self.warn(format!("{path:?} could not be read: {e}"));
+                return Err(e);
+            }
+            Err(e) => Some(e),
+        };
+        let corrupted_path = Self::with_suffix(path, "corrupted");
+        let reason = match &error {
+            Some(error) => {
+                error!("Unable to read {path:?}: {error}");
+                fs::rename(path, &corrupted_path).ok();
+                format!("could not be read ({error})")
+            }
+            None => "is missing".to_string(),
+        };
+        for index in 1..=Self::BACKUPS {
+            let backup_path = Self::backup_path(path, index);
+            if let Ok(Some(value)) = Self::read_file(&backup_path, &parse) {
+                Self::restore(&backup_path, path)?;
+                self.warn(format!(
+                    "{path:?} {reason}, it was restored from {backup_path:?}"
+                ));
+                return Ok(Some(value));
+            }
+        }
+        match error {
+            Some(error) => {
+                self.warn(format!(
+                    "{path:?} {reason} and there is no valid backup, it was kept as {corrupted_path:?}"
+                ));
+                Err(error)
+            }
+            None => {
+                self.warn(format!("{path:?} {reason} and there is no valid backup"));
+                Ok(None)
+            }
+        }
+    }
+
+    fn has_backup(&self, path: &Path) -> bool {
+        (1..=Self::BACKUPS).any(|index| Self::backup_path(path, index).exists())
+    }
+
+    /// Copy the backup in place of the file, through a temporary file like a save
+    fn restore(backup_path: &Path, path: &Path) -> Result<(), MyError> {
+        let temp_path = Self::with_suffix(path, "tmp");
+        fs::copy(backup_path, &temp_path)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    fn read_file<T>(
+        path: &Path,
+        parse: impl Fn(Value) -> Result<T, MyError>,
+    ) -> Result<Option<T>, MyError> {
+        match File::open(path) {
+            Ok(file) => Ok(Some(parse(serde_json::from_reader(BufReader::new(file))?)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Upgrade the cache to the current version, returns it with its original version
+    fn parse_cache(cache: Value) -> Result<(RoadworkData, u64), MyError> {
+        let version = migration::version(&cache);
+        Ok((serde_json::from_value(migration::migrate(cache)?)?, version))
+    }
+
+    fn read_cache(&self, path: &Path) -> Result<Option<RoadworkData>, MyError> {
+        match self.read_json(path, Self::parse_cache)? {
+            Some((roadwork_data, version)) => {
+                if version < migration::CURRENT_VERSION {
+                    info!(
+                        "Upgrade {path:?} from version {version} to {}",
+                        migration::CURRENT_VERSION
+                    );
+                    self.save(&roadwork_data)?;
+                }
+                Ok(Some(roadwork_data))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Write the json in a temporary file that replaces the file once complete,
+    /// so a crash while saving never leaves a truncated file
+    pub(crate) fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), MyError> {
+        info!("save to {path:?}");
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = Self::with_suffix(path, "tmp");
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        serde_json::to_writer_pretty(&mut writer, value)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        drop(writer);
+        Self::backup(path)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Rotate the backups and copy the current file as the most recent one
+    fn backup(path: &Path) -> Result<(), MyError> {
+        if !path.exists() {
+            return Ok(());
+        }
+        let last_backup_age = fs::metadata(Self::backup_path(path, 1))
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+        if last_backup_age.is_some_and(|age| age < Self::BACKUP_INTERVAL) {
+            return Ok(());
+        }
+        for index in (1..Self::BACKUPS).rev() {
+            let backup_path = Self::backup_path(path, index);
+            if backup_path.exists() {
+                fs::rename(&backup_path, Self::backup_path(path, index + 1))?;
+            }
+        }
+        fs::copy(path, Self::backup_path(path, 1))?;
+        Ok(())
+    }
+}
+
+impl RoadworkStore for JsonStore {
+    fn load(&self, source: &str) -> Result<Option<RoadworkData>, MyError> {
+        if let Some(roadwork_data) = self.read_cache(&self.get_path(source))? {
+            return Ok(Some(roadwork_data));
+        }
+        let legacy_path = self.get_legacy_path(source);
+        let roadwork_data = self.read_cache(&legacy_path)?;
+        if roadwork_data.is_some() {
+            fs::rename(&legacy_path, Self::with_suffix(&legacy_path, "migrated"))?;
+        }
+        Ok(roadwork_data)
+    }
+
+    fn save(&self, roadwork_data: &RoadworkData) -> Result<(), MyError> {
+        let mut cache = serde_json::to_value(roadwork_data)?;
+        cache["version"] = json!(migration::CURRENT_VERSION);
+        Self::write_json(&self.get_path(&roadwork_data.source), &cache)
+    }
+
+    fn load_history(&self, source: &str) -> Result<RoadworkHistory, MyError> {
+        Ok(self
+            .read_json(&self.get_history_path(source), |history| {
+                Ok(serde_json::from_value(history)?)
+            })?
+            .unwrap_or_else(|| RoadworkHistory::new(source)))
+    }
+
+    fn save_history(&self, history: &RoadworkHistory) -> Result<(), MyError> {
+        Self::write_json(&self.get_history_path(&history.source), history)
+    }
+
+    fn take_warnings(&self) -> Vec<String> {
+        std::mem::take(&mut self.warnings.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::roadwork::Roadwork;
+
+    fn roadwork_data(ids: &[&str]) -> RoadworkData {
+        let roadworks = ids
+            .iter()
+            .map(|id| Roadwork {
+                id: id.to_string(),
+                ..Roadwork::default()
+            })
+            .collect();
+        RoadworkData::new("France-Paris", roadworks)
+    }
+
+    #[test]
+    fn test_restore_backup() -> Result<(), MyError> {
+        let folder = tempfile::tempdir()?;
+        let store = JsonStore::new(folder.path().to_path_buf());
+        store.save(&roadwork_data(&["1"]))?;
+        store.save(&roadwork_data(&["1", "2"]))?;
+        let path = store.get_path("France-Paris");
+        assert!(JsonStore::backup_path(&path, 1).exists());
+        assert!(!JsonStore::with_suffix(&path, "tmp").exists());
+
+        fs::write(&path, "{\"source\": \"France-")?;
+        let restored = store.load("France-Paris")?.unwrap();
+        assert_eq!(restored.roadworks.len(), 1);
+        assert_eq!(store.take_warnings().len(), 1);
+        assert!(JsonStore::with_suffix(&path, "corrupted").exists());
+        // the backup was written back, the next load reads it without warning
+        assert_eq!(store.load("France-Paris")?.unwrap().roadworks.len(), 1);
+        assert!(store.take_warnings().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_missing_file() -> Result<(), MyError> {
+        let folder = tempfile::tempdir()?;
+        let store = JsonStore::new(folder.path().to_path_buf());
+        store.save(&roadwork_data(&["1"]))?;
+        store.save(&roadwork_data(&["1", "2"]))?;
+        let path = store.get_path("France-Paris");
+        fs::remove_file(&path)?;
+        assert_eq!(store.load("France-Paris")?.unwrap().roadworks.len(), 1);
+        assert_eq!(store.take_warnings().len(), 1);
+        assert!(path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_upgrade_legacy_cache() -> Result<(), MyError> {
+        let folder = tempfile::tempdir()?;
+        let store = JsonStore::new(folder.path().to_path_buf());
+        let legacy_path = store.get_legacy_path("France-Paris");
+        serde_json::to_writer(File::create(&legacy_path)?, &roadwork_data(&["1"]))?;
+        assert_eq!(store.load("France-Paris")?.unwrap().roadworks.len(), 1);
+        assert!(!legacy_path.exists());
+        let cache: Value = serde_json::from_reader(File::open(store.get_path("France-Paris"))?)?;
+        assert_eq!(migration::version(&cache), migration::CURRENT_VERSION);
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupted_without_backup() -> Result<(), MyError> {
+        let folder = tempfile::tempdir()?;
+        let store = JsonStore::new(folder.path().to_path_buf());
+        let path = store.get_path("France-Paris");
+        fs::write(&path, "not json")?;
+        assert!(store.load("France-Paris").is_err());
+        assert_eq!(store.take_warnings().len(), 1);
+        assert!(store.load("France-Paris")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_newer_version_is_kept() -> Result<(), MyError> {
+        let folder = tempfile::tempdir()?;
+        let store = JsonStore::new(folder.path().to_path_buf());
+        store.save(&roadwork_data(&["1"]))?;
+        store.save(&roadwork_data(&["1", "2"]))?;
+        let path = store.get_path("France-Paris");
+        let newer = json!({"version": migration::CURRENT_VERSION + 1, "roadworks": {}});
+        fs::write(&path, newer.to_string())?;
+        assert!(matches!(
+            store.load("France-Paris"),
+            Err(MyError::UnsupportedVersion(..))
+        ));
+        assert_eq!(store.take_warnings().len(), 1);
+        assert!(!JsonStore::with_suffix(&path, "corrupted").exists());
+        let kept: Value = serde_json::from_reader(File::open(&path)?)?;
+        assert_eq!(kept, newer);
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+use crate::MyError;
+use roadwork_sync::SyncData;
+use serde_json::{Map, Value, json};
+
+/// The version written in the header of the caches
+pub(crate) const CURRENT_VERSION: u64 = 3;
+
+/// Upgrade a cache to the next version, the index is the version it upgrades from minus one
+type Migration = fn(Value) -> Result<Value, MyError>;
+
+const MIGRATIONS: [Migration; 2] = [v1_to_v2, v2_to_v3];
+
+/// Returns the version of the cache.
+/// The versions 1 (written by the Java Roadwork app) and 2 have no header,
+/// they are recognized by the way they store the roadworks and the creation date
+pub(crate) fn version(cache: &Value) -> u64 {
+    match cache.get("version").and_then(Value::as_u64) {
+        Some(version) => version,
+        None if cache["roadworks"].is_object() && cache["created"].is_object() => 2,
+        None => 1,
+    }
+}
+
+/// Apply the migrations needed to bring the cache to the current version
+pub(crate) fn migrate(mut cache: Value) -> Result<Value, MyError> {
+    let mut version = version(&cache);
+    if version > CURRENT_VERSION {
+        return Err(MyError::UnsupportedVersion(version, CURRENT_VERSION));
+    }
+    if version == 0 {
+        return Err(MyError::ParsingError(format!(
+            "The cache version {version} is not supported, the current version is {CURRENT_VERSION}"
+        )));
+    }
+    while version < CURRENT_VERSION {
+        cache = MIGRATIONS[version as usize - 1](cache)?;
+        version += 1;
+    }
+    Ok(cache)
+}
+
+/// The Java app stored the roadworks in a list, the creation date in milliseconds,
+/// and the polygons as java.awt.Polygon whose arrays can be longer than their point count
+fn v1_to_v2(mut cache: Value) -> Result<Value, MyError> {
+    let object = cache
+        .as_object_mut()
+        .ok_or_else(|| MyError::ParsingError("The cache is not a json object".to_string()))?;
+    if let Some(created) = object.get("created").and_then(Value::as_u64) {
+        object.insert(
+            "created".to_string(),
+            json!({"secs": created / 1000, "nanos": (created % 1000) * 1_000_000}),
+        );
+    }
+    let roadworks = match object.remove("roadworks") {
+        Some(Value::Array(roadworks)) => roadworks,
+        Some(Value::Object(roadworks)) => roadworks.into_iter().map(|(_, r)| r).collect(),
+        _ => Vec::new(),
+    };
+    let mut roadworks_map = Map::new();
+    for mut roadwork in roadworks {
+        let Some(fields) = roadwork.as_object_mut() else {
+            continue;
+        };
+        fields.entry("url").or_insert_with(|| json!(""));
+        if !fields.contains_key("syncData") {
+            fields.insert(
+                "syncData".to_string(),
+                serde_json::to_value(SyncData::default())?,
+            );
+        }
+        if let Some(Value::Array(polygons)) = fields.get_mut("polygons") {
+            polygons.iter_mut().for_each(truncate_polygon);
+        }
+        if let Some(id) = fields.get("id").and_then(Value::as_str) {
+            roadworks_map.insert(id.to_string(), roadwork);
+        }
+    }
+    object.insert("roadworks".to_string(), Value::Object(roadworks_map));
+    Ok(cache)
+}
+
+fn truncate_polygon(polygon: &mut Value) {
+    let Some(npoints) = polygon.get("npoints").and_then(Value::as_u64) else {
+        return;
+    };
+    for points in ["xpoints", "ypoints"] {
+        if let Some(Value::Array(points)) = polygon.get_mut(points) {
+            points.truncate(npoints as usize);
+        }
+    }
+}
+
+/// The version 3 only adds the header
+fn v2_to_v3(mut cache: Value) -> Result<Value, MyError> {
+    if let Some(object) = cache.as_object_mut() {
+        object.insert("version".to_string(), json!(3));
+    }
+    Ok(cache)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::roadwork_data::RoadworkData;
+    use roadwork_sync::Status;
+
+    #[test]
+    fn test_migrate_java_cache() -> Result<(), MyError> {
+        let cache = json!({
+            "source": "France-Paris",
+            "created": 1700000000123u64,
+            "roadworks": [{
+                "id": "1",
+                "latitude": 48.85,
+                "longitude": 2.35,
+                "start": 1700000000000i64,
+                "end": 1800000000000i64,
+                "polygons": [{"npoints": 2, "xpoints": [1.0, 2.0, 0.0, 0.0], "ypoints": [3.0, 4.0, 0.0, 0.0]}],
+                "syncData": {"localUpdateTime": 0, "serverUpdateTime": 0, "status": "Treated", "dirty": false}
+            }]
+        });
+        assert_eq!(version(&cache), 1);
+        let migrated = migrate(cache)?;
+        assert_eq!(version(&migrated), CURRENT_VERSION);
+        let roadwork_data: RoadworkData = serde_json::from_value(migrated)?;
+        assert_eq!(roadwork_data.created.as_millis(), 1700000000123);
+        let roadwork = &roadwork_data.roadworks["1"];
+        assert_eq!(roadwork.sync_data.status, Status::Treated);
+        assert_eq!(
+            roadwork.polygons.as_ref().unwrap()[0].xpoints,
+            vec![1.0, 2.0]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_v2_cache() -> Result<(), MyError> {
+        let cache = serde_json::to_value(RoadworkData::new("France-Paris", Vec::new()))?;
+        assert_eq!(version(&cache), 2);
+        assert_eq!(version(&migrate(cache)?), CURRENT_VERSION);
+        assert!(matches!(
+            migrate(json!({"version": CURRENT_VERSION + 1})),
+            Err(MyError::UnsupportedVersion(_, CURRENT_VERSION))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+pub(crate) mod json_store;
+mod migration;
+pub(crate) mod sqlite_store;
+
+use crate::MyError;
+use crate::model::roadwork_data::RoadworkData;
+use crate::model::roadwork_history::RoadworkHistory;
+use crate::settings::Settings;
+use crate::store::json_store::JsonStore;
+use crate::store::sqlite_store::SqliteStore;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// The kind of storage used to keep the roadworks between two launches
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum Storage {
+    /// One json file per opendata service in the settings folder
+    #[default]
+    Json,
+    /// A single SQLite database in the settings folder
+    Sqlite,
+}
+
+/// Where the roadworks, their synchronization state and their history are persisted
+pub(crate) trait RoadworkStore: Send + Sync {
+    /// Returns the saved roadworks of the source, None if it was never saved
+    fn load(&self, source: &str) -> Result<Option<RoadworkData>, MyError>;
+
+    fn save(&self, roadwork_data: &RoadworkData) -> Result<(), MyError>;
+
+    /// Save the synchronization state of some roadworks after a local change,
+    /// stores that can't update it alone save everything
+    fn save_sync_data(&self, roadwork_data: &RoadworkData, _ids: &[&str]) -> Result<(), MyError> {
+        self.save(roadwork_data)
+    }
+
+    fn load_history(&self, source: &str) -> Result<RoadworkHistory, MyError>;
+
+    fn save_history(&self, history: &RoadworkHistory) -> Result<(), MyError>;
+
+    /// Keep track of a call to the opendata service, stores that can't do it ignore it
+    fn record_fetch(&self, _fetch_run: &FetchRun) -> Result<(), MyError> {
+        Ok(())
+    }
+
+    /// Returns the problems the user has to be told about, like a cache restored from a backup
+    fn take_warnings(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// The result of a call to an opendata service
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FetchRun {
+    pub(crate) source: String,
+    pub(crate) time: Duration,
+    /// The number of roadworks read from the service
+    pub(crate) roadworks: usize,
+    /// The number of records that were dropped while parsing
+    pub(crate) rejected: usize,
+    pub(crate) error: Option<String>,
+}
+
+/// Open the store selected in the settings in the folder.
+/// If the SQLite database can't be opened the json files are used instead
+pub(crate) fn open(settings: &Settings, folder: PathBuf) -> Box<dyn RoadworkStore> {
+    match settings.storage {
+        Storage::Json => Box::new(JsonStore::new(folder)),
+        Storage::Sqlite => match SqliteStore::open(&folder) {
+            Ok(store) => {
+                info!("Using the SQLite store in {folder:?}");
+                Box::new(store)
+            }
+            Err(e) => {
+                error!("Unable to open the SQLite store in {folder:?}, using json files: {e}");
+                Box::new(JsonStore::new(folder))
+            }
+        },
+    }
+}
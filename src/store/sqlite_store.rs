@@ -0,0 +1,468 @@
+use crate::MyError;
+use crate::model::roadwork::Roadwork;
+use crate::model::roadwork_data::RoadworkData;
+use crate::model::roadwork_history::{ArchivedRoadwork, RoadworkHistory};
+use crate::model::validators::Validators;
+use crate::store::json_store::JsonStore;
+use crate::store::{FetchRun, RoadworkStore};
+use log::{error, info};
+use rusqlite::{Connection, OptionalExtension, Transaction, params};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS source (
+    name TEXT PRIMARY KEY,
+    created INTEGER NOT NULL,
+    diagnostics TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS roadwork (
+    source TEXT NOT NULL,
+    id TEXT NOT NULL,
+    data TEXT NOT NULL,
+    PRIMARY KEY (source, id)
+);
+CREATE TABLE IF NOT EXISTS sync_data (
+    source TEXT NOT NULL,
+    id TEXT NOT NULL,
+    status TEXT NOT NULL,
+    data TEXT NOT NULL,
+    PRIMARY KEY (source, id)
+);
+CREATE TABLE IF NOT EXISTS history (
+    source TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    id TEXT NOT NULL,
+    status TEXT NOT NULL,
+    removed INTEGER NOT NULL,
+    data TEXT NOT NULL,
+    expired INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (source, position)
+);
+CREATE TABLE IF NOT EXISTS fetch_run (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source TEXT NOT NULL,
+    time INTEGER NOT NULL,
+    roadworks INTEGER NOT NULL,
+    rejected INTEGER NOT NULL,
+    error TEXT
+);
+CREATE TABLE IF NOT EXISTS validator (
+    source TEXT PRIMARY KEY,
+    etag TEXT,
+    last_modified TEXT
+);
+CREATE TABLE IF NOT EXISTS json_import (
+    source TEXT PRIMARY KEY
+);
+";
+
+/// Store every service in a single SQLite database.
+/// The synchronization state of the roadworks is kept in its own table so it can be
+/// updated without rewriting the roadworks, and a save only writes the rows that changed.
+/// The json files of a service are imported the first time it is read.
+pub(crate) struct SqliteStore {
+    connection: Mutex<Connection>,
+    /// The folder of the json files to import
+    json_folder: PathBuf,
+}
+
+impl SqliteStore {
+    const FILE_NAME: &'static str = "roadwork.db";
+
+    /// Open or create the database in the folder
+    pub(crate) fn open(folder: &Path) -> Result<Self, MyError> {
+        fs::create_dir_all(folder)?;
+        let connection = Connection::open(folder.join(Self::FILE_NAME))?;
+        connection.execute_batch(SCHEMA)?;
+        Self::add_history_expired(&connection)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+            json_folder: folder.to_path_buf(),
+        })
+    }
+
+    /// The databases created before the expired roadworks were archived have no expired column
+    fn add_history_expired(connection: &Connection) -> Result<(), MyError> {
+        let has_expired = connection
+            .prepare("SELECT expired FROM history LIMIT 0")
+            .is_ok();
+        if !has_expired {
+            connection.execute(
+                "ALTER TABLE history ADD COLUMN expired INTEGER NOT NULL DEFAULT 0",
+                [],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Import the json files of the source if it was not done yet.
+    /// A json file that can't be read is skipped, it won't be imported later
+    fn import_json(&self, source: &str) -> Result<(), MyError> {
+        let imported = self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT 1 FROM json_import WHERE source = ?1",
+                params![source],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if imported {
+            return Ok(());
+        }
+        let json_store = JsonStore::new(self.json_folder.clone());
+        match json_store.load(source) {
+            Ok(Some(roadwork_data)) => {
+                info!("Import {:?}", json_store.get_path(source));
+                self.save(&roadwork_data)?;
+            }
+            Ok(None) => {}
+            Err(e) => error!("Unable to import {:?}: {e}", json_store.get_path(source)),
+        }
+        match json_store.load_history(source) {
+            Ok(history) if !history.roadworks.is_empty() => {
+                info!("Import {:?}", json_store.get_history_path(source));
+                self.save_history(&history)?;
+            }
+            Ok(_) => {}
+            Err(e) => error!(
+                "Unable to import {:?}: {e}",
+                json_store.get_history_path(source)
+            ),
+        }
+        self.connection.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO json_import (source) VALUES (?1)",
+            params![source],
+        )?;
+        Ok(())
+    }
+
+    /// Write the synchronization state of the roadworks that changed, returns the number of rows written
+    fn upsert_sync_data<'a>(
+        transaction: &Transaction,
+        source: &str,
+        roadworks: impl Iterator<Item = &'a Roadwork>,
+    ) -> Result<usize, MyError> {
+        let mut upsert = transaction.prepare(
+            "INSERT INTO sync_data (source, id, status, data) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (source, id) DO UPDATE SET status = excluded.status, data = excluded.data
+             WHERE data <> excluded.data",
+        )?;
+        let mut written = 0;
+        for roadwork in roadworks {
+            let status: &str = roadwork.sync_data.status.into();
+            written += upsert.execute(params![
+                source,
+                roadwork.id,
+                status,
+                serde_json::to_string(&roadwork.sync_data)?
+            ])?;
+        }
+        Ok(written)
+    }
+
+    fn to_millis(duration: Duration) -> i64 {
+        duration.as_millis() as i64
+    }
+}
+
+impl RoadworkStore for SqliteStore {
+    fn load(&self, source: &str) -> Result<Option<RoadworkData>, MyError> {
+        self.import_json(source)?;
+        let connection = self.connection.lock().unwrap();
+        let Some((created, diagnostics)) = connection
+            .query_row(
+                "SELECT created, diagnostics FROM source WHERE name = ?1",
+                params![source],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        let mut statement = connection.prepare(
+            "SELECT roadwork.data, sync_data.data FROM roadwork
+             LEFT JOIN sync_data ON sync_data.source = roadwork.source AND sync_data.id = roadwork.id
+             WHERE roadwork.source = ?1",
+        )?;
+        let rows = statement.query_map(params![source], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+        })?;
+        let mut roadworks = Vec::new();
+        for row in rows {
+            let (data, sync_data) = row?;
+            let mut value: Value = serde_json::from_str(&data)?;
+            if let (Some(object), Some(sync_data)) = (value.as_object_mut(), sync_data) {
+                object.insert("syncData".to_string(), serde_json::from_str(&sync_data)?);
+            }
+            roadworks.push(serde_json::from_value::<Roadwork>(value)?);
+        }
+        let validators = connection
+            .query_row(
+                "SELECT etag, last_modified FROM validator WHERE source = ?1",
+                params![source],
+                |row| {
+                    Ok(Validators {
+                        etag: row.get(0)?,
+                        last_modified: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?;
+        let mut roadwork_data = RoadworkData::new(source, roadworks);
+        roadwork_data.created = Duration::from_millis(created as u64);
+        roadwork_data.diagnostics = serde_json::from_str(&diagnostics)?;
+        roadwork_data.validators = validators.unwrap_or_default();
+        Ok(Some(roadwork_data))
+    }
+
+    /// Only the roadworks that changed since the last save are written, the removed ones are deleted
+    fn save(&self, roadwork_data: &RoadworkData) -> Result<(), MyError> {
+        info!("save {} to the SQLite store", roadwork_data.source);
+        let source = &roadwork_data.source;
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "INSERT OR REPLACE INTO source (name, created, diagnostics) VALUES (?1, ?2, ?3)",
+            params![
+                source,
+                Self::to_millis(roadwork_data.created),
+                serde_json::to_string(&roadwork_data.diagnostics)?
+            ],
+        )?;
+        transaction.execute(
+            "INSERT OR REPLACE INTO validator (source, etag, last_modified) VALUES (?1, ?2, ?3)",
+            params![
+                source,
+                roadwork_data.validators.etag,
+                roadwork_data.validators.last_modified
+            ],
+        )?;
+        let removed = {
+            let mut statement = transaction.prepare("SELECT id FROM roadwork WHERE source = ?1")?;
+            let ids = statement.query_map(params![source], |row| row.get::<_, String>(0))?;
+            ids.filter(|id| {
+                id.as_ref()
+                    .is_ok_and(|id| !roadwork_data.roadworks.contains_key(id))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+        };
+        let mut written = removed.len();
+        {
+            let mut delete_roadwork =
+                transaction.prepare("DELETE FROM roadwork WHERE source = ?1 AND id = ?2")?;
+            let mut delete_sync_data =
+                transaction.prepare("DELETE FROM sync_data WHERE source = ?1 AND id = ?2")?;
+            for id in &removed {
+                delete_roadwork.execute(params![source, id])?;
+                delete_sync_data.execute(params![source, id])?;
+            }
+            let mut upsert_roadwork = transaction.prepare(
+                "INSERT INTO roadwork (source, id, data) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (source, id) DO UPDATE SET data = excluded.data
+                 WHERE data <> excluded.data",
+            )?;
+            for roadwork in roadwork_data.iter() {
+                let mut value = serde_json::to_value(roadwork)?;
+                if let Some(object) = value.as_object_mut() {
+                    object.remove("syncData");
+                }
+                written +=
+                    upsert_roadwork.execute(params![source, roadwork.id, value.to_string()])?;
+            }
+        }
+        written += Self::upsert_sync_data(&transaction, source, roadwork_data.iter())?;
+        transaction.commit()?;
+        info!("{written} rows of {source} written");
+        Ok(())
+    }
+
+    fn save_sync_data(&self, roadwork_data: &RoadworkData, ids: &[&str]) -> Result<(), MyError> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        Self::upsert_sync_data(
+            &transaction,
+            &roadwork_data.source,
+            ids.iter().filter_map(|id| roadwork_data.roadworks.get(*id)),
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn load_history(&self, source: &str) -> Result<RoadworkHistory, MyError> {
+        self.import_json(source)?;
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT removed, data, expired FROM history WHERE source = ?1 ORDER BY position",
+        )?;
+        let rows = statement.query_map(params![source], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, bool>(2)?,
+            ))
+        })?;
+        let mut history = RoadworkHistory::new(source);
+        for row in rows {
+            let (removed, data, expired) = row?;
+            history.roadworks.push(ArchivedRoadwork {
+                roadwork: serde_json::from_str(&data)?,
+                removed: Duration::from_millis(removed as u64),
+                expired,
+            });
+        }
+        Ok(history)
+    }
+
+    fn save_history(&self, history: &RoadworkHistory) -> Result<(), MyError> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "DELETE FROM history WHERE source = ?1",
+            params![history.source],
+        )?;
+        {
+            let mut insert = transaction.prepare(
+                "INSERT INTO history (source, position, id, status, removed, data, expired)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for (position, archived) in history.roadworks.iter().enumerate() {
+                let status: &str = archived.roadwork.sync_data.status.into();
+                insert.execute(params![
+                    history.source,
+                    position as i64,
+                    archived.roadwork.id,
+                    status,
+                    Self::to_millis(archived.removed),
+                    serde_json::to_string(&archived.roadwork)?,
+                    archived.expired
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn record_fetch(&self, fetch_run: &FetchRun) -> Result<(), MyError> {
+        self.connection.lock().unwrap().execute(
+            "INSERT INTO fetch_run (source, time, roadworks, rejected, error)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                fetch_run.source,
+                Self::to_millis(fetch_run.time),
+                fetch_run.roadworks as i64,
+                fetch_run.rejected as i64,
+                fetch_run.error
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use roadwork_sync::Status;
+
+    fn roadwork(id: &str, status: Status) -> Roadwork {
+        let mut roadwork = Roadwork {
+            id: id.to_string(),
+            road: Some(format!("Road {id}")),
+            ..Roadwork::default()
+        };
+        roadwork.sync_data.status = status;
+        roadwork
+    }
+
+    #[test]
+    fn test_save_and_load() -> Result<(), MyError> {
+        let folder = tempfile::tempdir()?;
+        let store = SqliteStore::open(folder.path())?;
+        assert!(store.load("France-Paris")?.is_none());
+
+        let mut roadwork_data = RoadworkData::new(
+            "France-Paris",
+            vec![roadwork("1", Status::Treated), roadwork("2", Status::New)],
+        );
+        roadwork_data.validators.etag = Some("\"33a64df5\"".to_string());
+        store.save(&roadwork_data)?;
+        let loaded = store.load("France-Paris")?.unwrap();
+        assert_eq!(loaded.roadworks.len(), 2);
+        assert_eq!(loaded.roadworks["1"].sync_data.status, Status::Treated);
+        assert_eq!(loaded.roadworks["2"].road.as_deref(), Some("Road 2"));
+        assert_eq!(loaded.validators, roadwork_data.validators);
+        assert_eq!(
+            loaded.created.as_millis(),
+            roadwork_data.created.as_millis()
+        );
+
+        let mut history = RoadworkHistory::new("France-Paris");
+        history.archive(vec![roadwork("3", Status::Ignored)]);
+        store.save_history(&history)?;
+        let loaded = store.load_history("France-Paris")?;
+        assert_eq!(loaded.roadworks.len(), 1);
+        assert_eq!(
+            loaded.roadworks[0].roadwork.sync_data.status,
+            Status::Ignored
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_only_changes_are_written() -> Result<(), MyError> {
+        let folder = tempfile::tempdir()?;
+        let store = SqliteStore::open(folder.path())?;
+        let mut roadwork_data = RoadworkData::new(
+            "France-Paris",
+            vec![roadwork("1", Status::New), roadwork("2", Status::New)],
+        );
+        store.save(&roadwork_data)?;
+        let total_changes = || store.connection.lock().unwrap().total_changes();
+
+        // only the source and its validators are written again
+        let before = total_changes();
+        store.save(&roadwork_data)?;
+        assert_eq!(total_changes() - before, 2);
+
+        roadwork_data
+            .get_mut_roadwork("1")
+            .unwrap()
+            .sync_data
+            .set_status(Status::Treated, 1000);
+        let before = total_changes();
+        store.save_sync_data(&roadwork_data, &["1"])?;
+        assert_eq!(total_changes() - before, 1);
+
+        roadwork_data.roadworks.remove("2");
+        store.save(&roadwork_data)?;
+        let loaded = store.load("France-Paris")?.unwrap();
+        assert_eq!(loaded.roadworks.len(), 1);
+        assert_eq!(loaded.roadworks["1"].sync_data.status, Status::Treated);
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_json() -> Result<(), MyError> {
+        let folder = tempfile::tempdir()?;
+        let json_store = JsonStore::new(folder.path().to_path_buf());
+        json_store.save(&RoadworkData::new(
+            "France-Paris",
+            vec![roadwork("1", Status::Later)],
+        ))?;
+
+        let store = SqliteStore::open(folder.path())?;
+        let loaded = store.load("France-Paris")?.unwrap();
+        assert_eq!(loaded.roadworks["1"].sync_data.status, Status::Later);
+
+        // the json file is imported only once
+        json_store.save(&RoadworkData::new("France-Paris", Vec::new()))?;
+        assert_eq!(store.load("France-Paris")?.unwrap().roadworks.len(), 1);
+        Ok(())
+    }
+}